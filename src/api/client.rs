@@ -1,17 +1,138 @@
-/// Claude API Client
-///
-/// Topic 4: HTTP Requests and API Basics
-/// Topic 5: The Anthropic API - system prompts, message history, roles
-/// Topic 6: Streaming Responses - SSE, real-time token display
-/// Topic 8: Tool Use / Function Calling
-
+//! Claude API Client
+//!
+//! Topic 4: HTTP Requests and API Basics
+//! Topic 5: The Anthropic API - system prompts, message history, roles
+//! Topic 6: Streaming Responses - SSE, real-time token display
+//! Topic 8: Tool Use / Function Calling
+
+use super::models::ModelInfo;
+use super::ratelimit::{self, RateLimiter};
+use super::tokens;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::io::{BufRead, BufReader};
+use std::time::Duration;
 
 const API_URL: &str = "https://api.anthropic.com/v1/messages";
+const COUNT_TOKENS_URL: &str = "https://api.anthropic.com/v1/messages/count_tokens";
 const API_VERSION: &str = "2023-06-01";
 
+/// Default maximum tokens the model may generate per response
+pub const MAX_TOKENS: u32 = 4096;
+
+/// Smallest thinking budget the API accepts
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// How many times a rate-limited (429) request is retried
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Wait used when a 429 carries no retry-after header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// How many times an overloaded model is retried before falling back
+const MAX_OVERLOAD_RETRIES: u32 = 2;
+
+/// First backoff after an overload; doubles on each retry
+const OVERLOAD_BACKOFF: Duration = Duration::from_secs(2);
+
+// ============================================================================
+// Client Configuration
+// ============================================================================
+
+/// Settings shared by every request
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub api_key: String,
+    pub model: ModelInfo,
+    pub max_tokens: u32,
+    /// Token budget for extended thinking (None = disabled)
+    pub thinking_budget: Option<u32>,
+    /// Shared view of the organization's rate limits
+    pub rate_limiter: RateLimiter,
+    /// Models to try, in order, when the primary model stays overloaded
+    pub fallback_models: Vec<ModelInfo>,
+    /// Values for the `anthropic-beta` header (e.g. "context-1m-2025-08-07")
+    pub betas: Vec<String>,
+    /// Additional headers sent with every request
+    pub extra_headers: Vec<(String, String)>,
+    /// Fields merged into the message request body, overriding typed ones
+    ///
+    /// An escape hatch for API features we have no typed support for yet.
+    pub extra_body: serde_json::Map<String, Value>,
+}
+
+impl ClientConfig {
+    /// Check the settings against the model's limits and capabilities
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_tokens == 0 || self.max_tokens > self.model.max_output_tokens {
+            return Err(format!(
+                "max_tokens {} is outside 1..={} supported by {}",
+                self.max_tokens, self.model.max_output_tokens, self.model.id
+            ));
+        }
+
+        if let Some(budget) = self.thinking_budget {
+            if !self.model.supports_thinking {
+                return Err(format!("{} does not support extended thinking", self.model.id));
+            }
+            if budget < MIN_THINKING_BUDGET || budget >= self.max_tokens {
+                return Err(format!(
+                    "thinking budget {} must be at least {} and below max_tokens ({})",
+                    budget, MIN_THINKING_BUDGET, self.max_tokens
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start a POST with authentication, version, beta and extra headers
+    fn post(&self, client: &reqwest::blocking::Client, url: &str) -> reqwest::blocking::RequestBuilder {
+        self.request(client, reqwest::Method::POST, url)
+    }
+
+    /// Start a request of any method with the headers every request carries
+    pub(super) fn request(
+        &self,
+        client: &reqwest::blocking::Client,
+        method: reqwest::Method,
+        url: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        let mut builder = client
+            .request(method, url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .header("content-type", "application/json");
+
+        if !self.betas.is_empty() {
+            builder = builder.header("anthropic-beta", self.betas.join(","));
+        }
+        for (name, value) in &self.extra_headers {
+            builder = builder.header(name, value);
+        }
+        builder
+    }
+
+    /// The same settings adapted to another model's limits
+    ///
+    /// max_tokens is clamped to what the model can produce, and thinking is
+    /// dropped if the model doesn't support it (or no longer fits).
+    fn for_model(&self, model: &ModelInfo) -> Self {
+        let max_tokens = self.max_tokens.min(model.max_output_tokens);
+        let thinking_budget = self
+            .thinking_budget
+            .filter(|&budget| model.supports_thinking && budget < max_tokens);
+
+        Self {
+            model: model.clone(),
+            max_tokens,
+            thinking_budget,
+            fallback_models: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
 // ============================================================================
 // Tool Definitions
 // ============================================================================
@@ -41,19 +162,24 @@ pub struct ToolCall {
     pub id: String,
     pub name: String,
     pub input: Value,
+    /// Set when the streamed input wasn't valid JSON (`input` is then `{}`)
+    pub input_error: Option<String>,
 }
 
 // ============================================================================
 // Message Types (now with content blocks for tool use)
 // ============================================================================
 
-/// Content block - can be text, tool_use, or tool_result
+/// Content block - can be text, image, tool_use, or tool_result
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 pub enum ContentBlock {
     #[serde(rename = "text")]
     Text { text: String },
 
+    #[serde(rename = "image")]
+    Image { source: ImageSource },
+
     #[serde(rename = "tool_use")]
     ToolUse {
         id: String,
@@ -64,8 +190,54 @@ pub enum ContentBlock {
     #[serde(rename = "tool_result")]
     ToolResult {
         tool_use_id: String,
-        content: String,
+        content: ToolResultContent,
+        /// Tells the model the tool failed, rather than returning this text
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        is_error: bool,
     },
+
+    #[serde(rename = "thinking")]
+    Thinking { thinking: String, signature: String },
+
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+}
+
+/// Image data sent inline (the only source kind we use)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageSource {
+    /// Always "base64"
+    #[serde(rename = "type")]
+    pub source_type: String,
+    /// e.g. "image/png"
+    pub media_type: String,
+    pub data: String,
+}
+
+/// What a tool returned: a plain string, or text and image blocks
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ToolResultContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl ToolResultContent {
+    /// The text parts, with a placeholder for each image
+    pub fn to_text(&self) -> String {
+        match self {
+            ToolResultContent::Text(text) => text.clone(),
+            ToolResultContent::Blocks(blocks) => blocks
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::Text { text } => text.clone(),
+                    ContentBlock::Image { source } => format!("[image: {}]", source.media_type),
+                    _ => String::new(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
 }
 
 /// A message in the conversation (supports both simple text and content blocks)
@@ -105,16 +277,24 @@ impl Message {
         }
     }
 
-    /// Create an assistant message with tool use (for reconstructing history)
-    pub fn assistant_tool_use(tool_calls: &[ToolCall]) -> Self {
-        let blocks: Vec<ContentBlock> = tool_calls
-            .iter()
-            .map(|tc| ContentBlock::ToolUse {
-                id: tc.id.clone(),
-                name: tc.name.clone(),
-                input: tc.input.clone(),
-            })
-            .collect();
+    /// Create an assistant message from a full response (for reconstructing history)
+    ///
+    /// Keeps thinking blocks, text and tool_use blocks in the order the API
+    /// expects them back.
+    pub fn assistant_response(response: &ChatResponse) -> Self {
+        let mut blocks: Vec<ContentBlock> = response.thinking.clone();
+
+        if !response.text.is_empty() {
+            blocks.push(ContentBlock::Text {
+                text: response.text.clone(),
+            });
+        }
+
+        blocks.extend(response.tool_calls.iter().map(|tc| ContentBlock::ToolUse {
+            id: tc.id.clone(),
+            name: tc.name.clone(),
+            input: tc.input.clone(),
+        }));
 
         Self {
             role: "assistant".to_string(),
@@ -122,19 +302,11 @@ impl Message {
         }
     }
 
-    /// Create a user message with tool results
-    pub fn tool_results(results: Vec<(String, String)>) -> Self {
-        let blocks: Vec<ContentBlock> = results
-            .into_iter()
-            .map(|(tool_use_id, content)| ContentBlock::ToolResult {
-                tool_use_id,
-                content,
-            })
-            .collect();
-
+    /// Create a user message with tool results (`ContentBlock::ToolResult`s)
+    pub fn tool_results(results: Vec<ContentBlock>) -> Self {
         Self {
             role: "user".to_string(),
-            content: MessageContent::Blocks { content: blocks },
+            content: MessageContent::Blocks { content: results },
         }
     }
 }
@@ -154,6 +326,69 @@ struct ApiRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<ThinkingConfig>,
+}
+
+/// Extended thinking settings sent with a request
+#[derive(Debug, Serialize)]
+struct ThinkingConfig {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    budget_tokens: u32,
+}
+
+impl ApiRequest {
+    /// Build a request, validating it against the model catalog entry first
+    fn new(
+        config: &ClientConfig,
+        messages: Vec<Message>,
+        system_prompt: Option<&str>,
+        tools: Vec<Tool>,
+    ) -> Result<Self, String> {
+        config.validate()?;
+
+        Ok(Self {
+            model: config.model.id.clone(),
+            max_tokens: config.max_tokens,
+            messages,
+            system: system_prompt.map(|s| s.to_string()),
+            stream: true,
+            tools,
+            thinking: config.thinking_budget.map(|budget_tokens| ThinkingConfig {
+                kind: "enabled",
+                budget_tokens,
+            }),
+        })
+    }
+
+    /// Serialize the request and merge in untyped extra fields
+    fn to_body(&self, extra: &serde_json::Map<String, Value>) -> Result<Value, String> {
+        let mut body = serde_json::to_value(self).map_err(|e| format!("Failed to serialize request: {}", e))?;
+        if let Value::Object(fields) = &mut body {
+            for (key, value) in extra {
+                fields.insert(key.clone(), value.clone());
+            }
+        }
+        Ok(body)
+    }
+}
+
+/// Body for the count_tokens endpoint (same shape as a message request,
+/// minus generation settings)
+#[derive(Debug, Serialize)]
+struct CountTokensRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[Tool]>::is_empty")]
+    tools: &'a [Tool],
+}
+
+#[derive(Debug, Deserialize)]
+struct CountTokensResponse {
+    input_tokens: u32,
 }
 
 /// Structured response from chat
@@ -162,6 +397,10 @@ pub struct ChatResponse {
     pub text: String,
     pub stop_reason: String,
     pub tool_calls: Vec<ToolCall>,
+    /// Thinking blocks, which must be sent back unchanged alongside tool use
+    pub thinking: Vec<ContentBlock>,
+    /// Model that actually produced the response (differs after a fallback)
+    pub model: String,
 }
 
 impl ChatResponse {
@@ -179,7 +418,6 @@ impl ChatResponse {
 struct StreamContentBlockStart {
     #[serde(rename = "type")]
     event_type: String,
-    index: usize,
     content_block: Option<StreamContentBlock>,
 }
 
@@ -189,22 +427,23 @@ struct StreamContentBlock {
     block_type: String,
     id: Option<String>,
     name: Option<String>,
+    /// Encrypted payload of a redacted_thinking block
+    data: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct StreamContentBlockDelta {
     #[serde(rename = "type")]
     event_type: String,
-    index: usize,
     delta: Option<StreamDelta>,
 }
 
 #[derive(Debug, Deserialize)]
 struct StreamDelta {
-    #[serde(rename = "type")]
-    delta_type: String,
     text: Option<String>,
     partial_json: Option<String>,
+    thinking: Option<String>,
+    signature: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -219,13 +458,44 @@ struct StreamMessageDeltaData {
     stop_reason: Option<String>,
 }
 
+/// `error` event sent in place of the rest of the stream
+#[derive(Debug, Deserialize)]
+struct StreamError {
+    #[serde(rename = "type")]
+    event_type: String,
+    error: Option<StreamErrorBody>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamErrorBody {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: Option<String>,
+}
+
+/// Why a single attempt failed: overloads are worth retrying elsewhere
+enum Failure {
+    Overloaded(String),
+    Fatal(String),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Fatal(message)
+    }
+}
+
 // ============================================================================
 // API Functions
 // ============================================================================
 
 /// Send messages with streaming and tool support
+///
+/// If the model is overloaded (HTTP 529 or an `overloaded_error` mid-stream),
+/// the request is retried with backoff, then re-issued on each of
+/// `config.fallback_models` in turn.
 pub fn send_messages_streaming<F>(
-    api_key: &str,
+    config: &ClientConfig,
     messages: Vec<Message>,
     system_prompt: Option<&str>,
     tools: Vec<Tool>,
@@ -234,29 +504,160 @@ pub fn send_messages_streaming<F>(
 where
     F: FnMut(&str),
 {
-    let request = ApiRequest {
-        model: "claude-sonnet-4-20250514".to_string(),
-        max_tokens: 4096,
-        messages,
-        system: system_prompt.map(|s| s.to_string()),
-        stream: true,
-        tools,
-    };
+    let models = std::iter::once(&config.model).chain(&config.fallback_models);
+    let mut last_error = String::new();
+    let mut previous: Option<&ModelInfo> = None;
+
+    for model in models {
+        if let Some(previous) = previous {
+            eprintln!("\r[{} is overloaded, falling back to {}]", previous.id, model.id);
+        }
+        previous = Some(model);
+
+        let attempt_config = config.for_model(model);
+
+        let attempt_messages = adapt_messages(&messages, model);
+
+        for retry in 0..=MAX_OVERLOAD_RETRIES {
+            if retry > 0 {
+                let backoff = OVERLOAD_BACKOFF * 2u32.pow(retry - 1);
+                eprintln!(
+                    "\r[{} is overloaded, retrying in {}s]",
+                    model.id,
+                    backoff.as_secs()
+                );
+                std::thread::sleep(backoff);
+            }
+
+            // An overload can cut a response off after part of it was shown
+            let mut printed = false;
+            let mut on_chunk = |text: &str| {
+                printed = true;
+                on_text_chunk(text);
+            };
+            match stream_once(
+                &attempt_config,
+                attempt_messages.clone(),
+                system_prompt,
+                tools.clone(),
+                &mut on_chunk,
+            ) {
+                Ok(response) => return Ok(response),
+                Err(Failure::Overloaded(message)) => {
+                    if printed {
+                        eprintln!("\n[response interrupted by an overload; a retry starts over from the beginning]");
+                    }
+                    last_error = message;
+                }
+                Err(Failure::Fatal(message)) => return Err(message),
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+/// The history with what `model` can't accept taken out: a model without
+/// extended thinking rejects thinking blocks, one without vision rejects
+/// images (e.g. a screenshot read_file returned)
+fn adapt_messages(messages: &[Message], model: &ModelInfo) -> Vec<Message> {
+    if model.supports_thinking && model.supports_vision {
+        return messages.to_vec();
+    }
+    messages
+        .iter()
+        .map(|message| match &message.content {
+            MessageContent::Blocks { content } => Message {
+                role: message.role.clone(),
+                content: MessageContent::Blocks {
+                    content: adapt_blocks(content, model),
+                },
+            },
+            MessageContent::Text { .. } => message.clone(),
+        })
+        .collect()
+}
+
+/// Drop thinking blocks and replace images with a note, as `model` requires,
+/// including images inside tool results
+fn adapt_blocks(blocks: &[ContentBlock], model: &ModelInfo) -> Vec<ContentBlock> {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } if !model.supports_thinking => None,
+            ContentBlock::Image { source } if !model.supports_vision => Some(ContentBlock::Text {
+                text: format!("[{} image left out: {} does not accept images]", source.media_type, model.id),
+            }),
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content: ToolResultContent::Blocks(content),
+                is_error,
+            } => Some(ContentBlock::ToolResult {
+                tool_use_id: tool_use_id.clone(),
+                content: ToolResultContent::Blocks(adapt_blocks(content, model)),
+                is_error: *is_error,
+            }),
+            _ => Some(block.clone()),
+        })
+        .collect()
+}
+
+/// One streaming request against one model
+fn stream_once<F>(
+    config: &ClientConfig,
+    messages: Vec<Message>,
+    system_prompt: Option<&str>,
+    tools: Vec<Tool>,
+    on_text_chunk: &mut F,
+) -> Result<ChatResponse, Failure>
+where
+    F: FnMut(&str),
+{
+    let request = ApiRequest::new(config, messages, system_prompt, tools)?;
+    let estimated_tokens =
+        tokens::estimate_request(&request.messages, request.system.as_deref(), &request.tools) as u64;
+    let body = request.to_body(&config.extra_body)?;
 
     let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(API_URL)
-        .header("x-api-key", api_key)
-        .header("anthropic-version", API_VERSION)
-        .header("content-type", "application/json")
-        .json(&request)
-        .send()
-        .map_err(|e| format!("HTTP request failed: {}", e))?;
+    let mut attempt = 0;
+    let response = loop {
+        // Wait here if this or another process has used up the budget
+        config.rate_limiter.throttle(estimated_tokens, |wait, kind| {
+            eprintln!(
+                "\r[rate limit: {} budget exhausted, waiting {}s]",
+                kind,
+                wait.as_secs().max(1)
+            );
+        });
+
+        let response = config
+            .post(&client, API_URL)
+            .json(&body)
+            .send()
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+        config.rate_limiter.record(response.headers());
+
+        // 429: remember when we may retry, so throttle() waits (for every process)
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS && attempt < MAX_RATE_LIMIT_RETRIES {
+            let wait = ratelimit::retry_after(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER);
+            config.rate_limiter.record_retry_after(wait);
+            attempt += 1;
+            continue;
+        }
+
+        break response;
+    };
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().unwrap_or_default();
-        return Err(format!("API error {}: {}", status, body));
+        let message = format!("API error {}: {}", status, body);
+        // 529 is the API's "overloaded" status
+        if status.as_u16() == 529 || body.contains("overloaded_error") {
+            return Err(Failure::Overloaded(message));
+        }
+        return Err(Failure::Fatal(message));
     }
 
     // Parse SSE stream
@@ -264,12 +665,16 @@ where
     let mut full_text = String::new();
     let mut stop_reason = "unknown".to_string();
     let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut thinking: Vec<ContentBlock> = Vec::new();
 
     // Track current tool being built (for streaming tool input)
     let mut current_tool_id: Option<String> = None;
     let mut current_tool_name: Option<String> = None;
     let mut current_tool_json = String::new();
 
+    // Track current thinking block: (thinking text, signature)
+    let mut current_thinking: Option<(String, String)> = None;
+
     for line in reader.lines() {
         let line = line.map_err(|e| format!("Read error: {}", e))?;
 
@@ -278,55 +683,103 @@ where
                 continue;
             }
 
-            // content_block_start - might be text or tool_use
-            if let Ok(event) = serde_json::from_str::<StreamContentBlockStart>(data) {
-                if event.event_type == "content_block_start" {
-                    if let Some(block) = event.content_block {
-                        if block.block_type == "tool_use" {
-                            current_tool_id = block.id;
-                            current_tool_name = block.name;
-                            current_tool_json.clear();
-                        }
+            // content_block_start - might be text, tool_use or thinking
+            if let Ok(event) = serde_json::from_str::<StreamContentBlockStart>(data)
+                && event.event_type == "content_block_start"
+                && let Some(block) = event.content_block
+            {
+                match block.block_type.as_str() {
+                    "tool_use" => {
+                        current_tool_id = block.id;
+                        current_tool_name = block.name;
+                        current_tool_json.clear();
                     }
+                    "thinking" => current_thinking = Some((String::new(), String::new())),
+                    "redacted_thinking" => thinking.push(ContentBlock::RedactedThinking {
+                        data: block.data.unwrap_or_default(),
+                    }),
+                    _ => {}
                 }
             }
 
             // content_block_delta - text or tool input JSON
-            if let Ok(event) = serde_json::from_str::<StreamContentBlockDelta>(data) {
-                if event.event_type == "content_block_delta" {
-                    if let Some(delta) = event.delta {
-                        // Text delta
-                        if let Some(text) = delta.text {
-                            on_text_chunk(&text);
-                            full_text.push_str(&text);
-                        }
-                        // Tool input JSON delta
-                        if let Some(json) = delta.partial_json {
-                            current_tool_json.push_str(&json);
-                        }
-                    }
+            if let Ok(event) = serde_json::from_str::<StreamContentBlockDelta>(data)
+                && event.event_type == "content_block_delta"
+                && let Some(delta) = event.delta
+            {
+                // Text delta
+                if let Some(text) = delta.text {
+                    on_text_chunk(&text);
+                    full_text.push_str(&text);
+                }
+                // Tool input JSON delta
+                if let Some(json) = delta.partial_json {
+                    current_tool_json.push_str(&json);
+                }
+                // Thinking text and its signature
+                if let Some((text, signature)) = current_thinking.as_mut() {
+                    text.push_str(delta.thinking.as_deref().unwrap_or_default());
+                    signature.push_str(delta.signature.as_deref().unwrap_or_default());
                 }
             }
 
             // content_block_stop - finalize tool if we were building one
-            if data.contains("\"type\":\"content_block_stop\"") {
-                if let (Some(id), Some(name)) = (current_tool_id.take(), current_tool_name.take()) {
-                    let input: Value = serde_json::from_str(&current_tool_json)
-                        .unwrap_or(Value::Object(serde_json::Map::new()));
-                    tool_calls.push(ToolCall { id, name, input });
-                    current_tool_json.clear();
+            if data.contains("\"type\":\"content_block_stop\"")
+                && let (Some(id), Some(name)) = (current_tool_id.take(), current_tool_name.take())
+            {
+                // A tool without parameters streams no input at all
+                let (input, input_error) = if current_tool_json.trim().is_empty() {
+                    (Value::Object(serde_json::Map::new()), None)
+                } else {
+                    match serde_json::from_str(&current_tool_json) {
+                        Ok(input) => (input, None),
+                        Err(e) => (
+                            Value::Object(serde_json::Map::new()),
+                            Some(format!("Tool input is not valid JSON ({}): {}", e, current_tool_json)),
+                        ),
+                    }
+                };
+                tool_calls.push(ToolCall {
+                    id,
+                    name,
+                    input,
+                    input_error,
+                });
+                current_tool_json.clear();
+            }
+
+            // content_block_stop - finalize thinking block
+            if data.contains("\"type\":\"content_block_stop\"")
+                && let Some((text, signature)) = current_thinking.take()
+            {
+                thinking.push(ContentBlock::Thinking {
+                    thinking: text,
+                    signature,
+                });
+            }
+
+            // error - the stream ends early (e.g. overloaded mid-response)
+            if let Ok(event) = serde_json::from_str::<StreamError>(data)
+                && event.event_type == "error"
+                && let Some(error) = event.error
+            {
+                let message = format!(
+                    "API error {}: {}",
+                    error.error_type,
+                    error.message.unwrap_or_default()
+                );
+                if error.error_type == "overloaded_error" {
+                    return Err(Failure::Overloaded(message));
                 }
+                return Err(Failure::Fatal(message));
             }
 
             // message_delta - stop_reason
-            if let Ok(event) = serde_json::from_str::<StreamMessageDelta>(data) {
-                if event.event_type == "message_delta" {
-                    if let Some(delta) = event.delta {
-                        if let Some(reason) = delta.stop_reason {
-                            stop_reason = reason;
-                        }
-                    }
-                }
+            if let Ok(event) = serde_json::from_str::<StreamMessageDelta>(data)
+                && event.event_type == "message_delta"
+                && let Some(reason) = event.delta.and_then(|d| d.stop_reason)
+            {
+                stop_reason = reason;
             }
         }
     }
@@ -335,15 +788,50 @@ where
         text: full_text,
         stop_reason,
         tool_calls,
+        thinking,
+        model: config.model.id.clone(),
     })
 }
 
 /// Send messages without streaming
 pub fn send_messages(
-    api_key: &str,
+    config: &ClientConfig,
     messages: Vec<Message>,
     system_prompt: Option<&str>,
     tools: Vec<Tool>,
 ) -> Result<ChatResponse, String> {
-    send_messages_streaming(api_key, messages, system_prompt, tools, |_| {})
+    send_messages_streaming(config, messages, system_prompt, tools, |_| {})
+}
+
+/// Ask the API how many input tokens a request would use, without running it
+pub fn count_tokens(
+    config: &ClientConfig,
+    messages: &[Message],
+    system_prompt: Option<&str>,
+    tools: &[Tool],
+) -> Result<u32, String> {
+    let request = CountTokensRequest {
+        model: &config.model.id,
+        messages,
+        system: system_prompt,
+        tools,
+    };
+
+    let client = reqwest::blocking::Client::new();
+    let response = config
+        .post(&client, COUNT_TOKENS_URL)
+        .json(&request)
+        .send()
+        .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        return Err(format!("API error {}: {}", status, body));
+    }
+
+    let body: CountTokensResponse = response
+        .json()
+        .map_err(|e| format!("Failed to parse count_tokens response: {}", e))?;
+    Ok(body.input_tokens)
 }
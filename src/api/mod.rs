@@ -1,10 +1,21 @@
-/// API module - handles communication with Claude API
-///
-/// Topic 4: HTTP Requests and API Basics
-/// Topic 5: The Anthropic API - system prompts, message history
-/// Topic 6: Streaming Responses
-/// Topic 8: Tool Use / Function Calling
+//! API module - handles communication with Claude API
+//!
+//! Topic 4: HTTP Requests and API Basics
+//! Topic 5: The Anthropic API - system prompts, message history, model catalog
+//! Topic 6: Streaming Responses
+//! Topic 8: Tool Use / Function Calling
+//! Topic 14: Context Window Strategies - token counting
+//! Topic 15: Error Handling and Recovery - rate limits
 
 mod client;
+mod models;
+mod ratelimit;
+mod tokens;
 
-pub use client::{send_messages_streaming, ChatResponse, Message, Tool, ToolCall};
+pub use client::{
+    count_tokens, send_messages, send_messages_streaming, ChatResponse, ClientConfig, ContentBlock,
+    ImageSource, Message, MessageContent, Tool, ToolResultContent, MAX_TOKENS,
+};
+pub use models::{ModelCatalog, DEFAULT_MODEL};
+pub use ratelimit::RateLimiter;
+pub use tokens::estimate_request;
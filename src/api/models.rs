@@ -0,0 +1,222 @@
+//! Model Catalog
+//!
+//! Topic 5: The Anthropic API - choosing a model
+//!
+//! A model id on its own says nothing about what the model can do. The
+//! catalog records, for each model, the limits and capabilities that decide
+//! whether a request is valid: context window, maximum output tokens,
+//! pricing, and support for extended thinking and vision.
+//!
+//! The built-in table covers the models we know about at compile time.
+//! `refresh` adds whatever else the `/v1/models` endpoint lists, borrowing
+//! limits from the closest known model of the same family.
+
+use super::client::ClientConfig;
+use serde::Deserialize;
+
+const MODELS_URL: &str = "https://api.anthropic.com/v1/models?limit=1000";
+
+/// Model used when none is requested
+pub const DEFAULT_MODEL: &str = "claude-sonnet-4-20250514";
+
+/// Model families that work as aliases for their newest member
+const FAMILIES: &[&str] = &["opus", "sonnet", "haiku"];
+
+/// Limits and capabilities of a single model
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+    /// Extra names that resolve to this model (e.g. "claude-sonnet-4-0")
+    pub aliases: Vec<String>,
+    pub context_window: usize,
+    pub max_output_tokens: u32,
+    /// USD per million input tokens
+    pub input_price: f64,
+    /// USD per million output tokens
+    pub output_price: f64,
+    pub supports_thinking: bool,
+    pub supports_vision: bool,
+}
+
+impl ModelInfo {
+    #[allow(clippy::too_many_arguments)]
+    fn builtin(
+        id: &str,
+        display_name: &str,
+        aliases: &[&str],
+        max_output_tokens: u32,
+        input_price: f64,
+        output_price: f64,
+        supports_thinking: bool,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            display_name: display_name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            context_window: 200_000,
+            max_output_tokens,
+            input_price,
+            output_price,
+            supports_thinking,
+            supports_vision: true,
+        }
+    }
+
+    /// Estimated cost in USD of a request with the given token counts
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 * self.input_price + output_tokens as f64 * self.output_price)
+            / 1_000_000.0
+    }
+
+    /// Multi-line description for the /model REPL command
+    pub fn describe(&self) -> String {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        format!(
+            "{} ({})\n  context window: {} tokens\n  max output:     {} tokens\n  pricing:        ${:.2} / ${:.2} per MTok (input / output)\n  thinking: {}  vision: {}",
+            self.display_name,
+            self.id,
+            self.context_window,
+            self.max_output_tokens,
+            self.input_price,
+            self.output_price,
+            yes_no(self.supports_thinking),
+            yes_no(self.supports_vision),
+        )
+    }
+
+    fn family(&self) -> Option<&'static str> {
+        FAMILIES.iter().copied().find(|f| self.id.contains(f))
+    }
+}
+
+/// All models the agent knows about, newest first
+#[derive(Debug, Clone)]
+pub struct ModelCatalog {
+    models: Vec<ModelInfo>,
+}
+
+/// One entry of the /v1/models response
+#[derive(Debug, Deserialize)]
+struct ApiModel {
+    id: String,
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiModelList {
+    data: Vec<ApiModel>,
+}
+
+impl ModelCatalog {
+    /// The compiled-in table of known models
+    pub fn builtin() -> Self {
+        let models = vec![
+            ModelInfo::builtin("claude-opus-4-5-20251101", "Claude Opus 4.5", &["claude-opus-4-5"], 64_000, 5.0, 25.0, true),
+            ModelInfo::builtin("claude-haiku-4-5-20251001", "Claude Haiku 4.5", &["claude-haiku-4-5"], 64_000, 1.0, 5.0, true),
+            ModelInfo::builtin("claude-sonnet-4-5-20250929", "Claude Sonnet 4.5", &["claude-sonnet-4-5"], 64_000, 3.0, 15.0, true),
+            ModelInfo::builtin("claude-opus-4-1-20250805", "Claude Opus 4.1", &["claude-opus-4-1"], 32_000, 15.0, 75.0, true),
+            ModelInfo::builtin("claude-opus-4-20250514", "Claude Opus 4", &["claude-opus-4-0"], 32_000, 15.0, 75.0, true),
+            ModelInfo::builtin("claude-sonnet-4-20250514", "Claude Sonnet 4", &["claude-sonnet-4-0"], 64_000, 3.0, 15.0, true),
+            ModelInfo::builtin("claude-3-7-sonnet-20250219", "Claude Sonnet 3.7", &["claude-3-7-sonnet-latest"], 64_000, 3.0, 15.0, true),
+            ModelInfo::builtin("claude-3-5-haiku-20241022", "Claude Haiku 3.5", &["claude-3-5-haiku-latest"], 8_192, 0.8, 4.0, false),
+        ];
+        Self { models }
+    }
+
+    /// Look up a model by id, alias, or family name ("sonnet" -> newest Sonnet)
+    pub fn resolve(&self, name: &str) -> Option<&ModelInfo> {
+        let name = name.trim().to_lowercase();
+
+        if let Some(model) = self
+            .models
+            .iter()
+            .find(|m| m.id == name || m.aliases.contains(&name))
+        {
+            return Some(model);
+        }
+
+        // Family alias: the catalog is ordered newest first
+        if FAMILIES.contains(&name.as_str()) {
+            return self.models.iter().find(|m| m.family() == Some(name.as_str()));
+        }
+
+        None
+    }
+
+    /// All known models, newest first
+    pub fn models(&self) -> &[ModelInfo] {
+        &self.models
+    }
+
+    /// Add models listed by the `/v1/models` endpoint that we don't know yet
+    ///
+    /// The endpoint only reports ids and names, so new models inherit limits
+    /// from the newest known model of the same family. The request carries
+    /// `config`'s key and headers. Returns how many models were added.
+    pub fn refresh(&mut self, config: &ClientConfig) -> Result<usize, String> {
+        let client = reqwest::blocking::Client::new();
+        let response = config
+            .request(&client, reqwest::Method::GET, MODELS_URL)
+            .send()
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().unwrap_or_default();
+            return Err(format!("API error {}: {}", status, body));
+        }
+
+        let list: ApiModelList = response
+            .json()
+            .map_err(|e| format!("Failed to parse model list: {}", e))?;
+
+        // The API lists newest first; keep that order for new entries
+        let mut added = Vec::new();
+        for api_model in list.data {
+            if self.models.iter().any(|m| m.id == api_model.id) {
+                continue;
+            }
+            added.push(self.infer(api_model));
+        }
+
+        let count = added.len();
+        added.append(&mut self.models);
+        self.models = added;
+        Ok(count)
+    }
+
+    /// Build an entry for an unknown model from its closest known relative
+    fn infer(&self, api_model: ApiModel) -> ModelInfo {
+        let family = FAMILIES.iter().copied().find(|f| api_model.id.contains(f));
+        let template = family.and_then(|f| self.models.iter().find(|m| m.family() == Some(f)));
+
+        let display_name = api_model.display_name.unwrap_or_else(|| api_model.id.clone());
+        match template {
+            Some(t) => ModelInfo {
+                id: api_model.id,
+                display_name,
+                aliases: Vec::new(),
+                ..t.clone()
+            },
+            // Unknown family: assume conservative limits and no extras
+            None => ModelInfo {
+                id: api_model.id,
+                display_name,
+                aliases: Vec::new(),
+                context_window: 200_000,
+                max_output_tokens: 8_192,
+                input_price: 0.0,
+                output_price: 0.0,
+                supports_thinking: false,
+                supports_vision: false,
+            },
+        }
+    }
+}
+
+impl Default for ModelCatalog {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
@@ -0,0 +1,265 @@
+//! Client-Side Rate Limiting
+//!
+//! Topic 15: Error Handling and Recovery - avoiding 429s
+//!
+//! Every API response reports how much of the organization's budget is left
+//! in `anthropic-ratelimit-*` headers. Several agent processes sharing one
+//! key (e.g. scripts calling `run_once` in a loop) each only see their own
+//! responses, so we keep the latest numbers in a small state file that all
+//! processes read and update under a lock file:
+//!
+//! 1. Before a request, reserve one request and the estimated input tokens.
+//!    If the budget is exhausted, sleep until it resets.
+//! 2. After a response, overwrite the state with the headers' numbers.
+//! 3. On a 429, honor `retry-after` by marking the budget as exhausted.
+
+use crate::time;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Lock files older than this are assumed to belong to a crashed process
+const STALE_LOCK: Duration = Duration::from_secs(10);
+
+/// Give up on the lock after this long and proceed unsynchronized
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Never sleep longer than this in one go, so resets are re-checked
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// One budget reported by the API (requests, tokens, ...)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Bucket {
+    limit: u64,
+    remaining: u64,
+    /// Unix time (seconds) at which the budget is fully replenished
+    reset: u64,
+}
+
+impl Bucket {
+    /// Seconds until this bucket can cover `needed`, or None if it already can
+    fn wait_for(&self, needed: u64, now: u64) -> Option<u64> {
+        if self.reset <= now || self.remaining >= needed {
+            None
+        } else {
+            Some(self.reset - now)
+        }
+    }
+
+    fn reserve(&mut self, amount: u64, now: u64) {
+        if self.reset > now {
+            self.remaining = self.remaining.saturating_sub(amount);
+        }
+    }
+}
+
+/// Everything we know about the shared budget
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RateLimitState {
+    requests: Option<Bucket>,
+    input_tokens: Option<Bucket>,
+}
+
+/// Tracks the organization's rate limits across processes
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    state_path: PathBuf,
+    lock_path: PathBuf,
+}
+
+impl RateLimiter {
+    /// A limiter whose state is shared by every process using this API key
+    pub fn for_key(api_key: &str) -> Self {
+        // Keyed by a hash so different keys don't share limits, and the key
+        // itself never lands on disk. The hash must not change between
+        // builds, or two versions of the agent would keep separate budgets.
+        let name = format!("ratelimit-{:016x}", fnv1a(api_key.as_bytes()));
+
+        let dir = state_dir();
+        Self {
+            state_path: dir.join(format!("{}.json", name)),
+            lock_path: dir.join(format!("{}.lock", name)),
+        }
+    }
+
+    /// Block until the shared budget can cover one more request of roughly
+    /// `estimated_tokens` input tokens, then reserve it
+    ///
+    /// `on_wait` is told how long we are about to sleep and why.
+    pub fn throttle<F>(&self, estimated_tokens: u64, mut on_wait: F)
+    where
+        F: FnMut(Duration, &str),
+    {
+        loop {
+            let wait = self.with_state(|state| {
+                let now = unix_now();
+                let request_wait = state.requests.as_ref().and_then(|b| b.wait_for(1, now));
+                let token_wait = state
+                    .input_tokens
+                    .as_ref()
+                    .and_then(|b| b.wait_for(estimated_tokens.min(b.limit), now));
+
+                match (request_wait, token_wait) {
+                    (None, None) => {
+                        if let Some(b) = state.requests.as_mut() {
+                            b.reserve(1, now);
+                        }
+                        if let Some(b) = state.input_tokens.as_mut() {
+                            b.reserve(estimated_tokens, now);
+                        }
+                        None
+                    }
+                    (Some(secs), _) => Some((secs, "request")),
+                    (None, Some(secs)) => Some((secs, "input token")),
+                }
+            });
+
+            match wait {
+                None => return,
+                Some((secs, kind)) => {
+                    let duration = Duration::from_secs(secs).min(MAX_WAIT);
+                    on_wait(duration, kind);
+                    thread::sleep(duration);
+                }
+            }
+        }
+    }
+
+    /// Update the shared state from a response's rate limit headers
+    pub fn record(&self, headers: &HeaderMap) {
+        let requests = parse_bucket(headers, "requests");
+        let input_tokens = parse_bucket(headers, "input-tokens").or_else(|| parse_bucket(headers, "tokens"));
+
+        if requests.is_none() && input_tokens.is_none() {
+            return;
+        }
+
+        self.with_state(|state| {
+            if requests.is_some() {
+                state.requests = requests;
+            }
+            if input_tokens.is_some() {
+                state.input_tokens = input_tokens;
+            }
+        });
+    }
+
+    /// Mark the request budget as exhausted for `retry_after` (after a 429)
+    pub fn record_retry_after(&self, retry_after: Duration) {
+        let reset = unix_now() + retry_after.as_secs().max(1);
+        self.with_state(|state| {
+            let bucket = state.requests.get_or_insert_with(Bucket::default);
+            bucket.remaining = 0;
+            bucket.reset = bucket.reset.max(reset);
+        });
+    }
+
+    /// Run `f` on the shared state while holding the lock file
+    fn with_state<T>(&self, f: impl FnOnce(&mut RateLimitState) -> T) -> T {
+        let locked = self.lock();
+
+        let mut state: RateLimitState = fs::read_to_string(&self.state_path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        let result = f(&mut state);
+
+        // Write to a temp file and rename so readers never see a partial file
+        if let Ok(json) = serde_json::to_string(&state) {
+            let tmp = self.state_path.with_extension(format!("tmp{}", std::process::id()));
+            if fs::write(&tmp, json).is_ok() {
+                let _ = fs::rename(&tmp, &self.state_path);
+            }
+        }
+
+        if locked {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+        result
+    }
+
+    /// Acquire the lock file; returns false if we gave up waiting for it
+    fn lock(&self) -> bool {
+        let _ = fs::create_dir_all(self.lock_path.parent().unwrap_or(&self.lock_path));
+        let start = SystemTime::now();
+
+        loop {
+            // create_new is atomic: exactly one process can create the file
+            match OpenOptions::new().write(true).create_new(true).open(&self.lock_path) {
+                Ok(_) => return true,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&self.lock_path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|t| t.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK);
+                    if stale {
+                        let _ = fs::remove_file(&self.lock_path);
+                        continue;
+                    }
+                }
+                Err(_) => return false,
+            }
+
+            if start.elapsed().unwrap_or_default() > LOCK_TIMEOUT {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+/// Parse the `retry-after` header (seconds) of a 429 response
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(|secs| Duration::from_secs_f64(secs.max(0.0)))
+}
+
+/// Read `anthropic-ratelimit-<name>-{limit,remaining,reset}`
+fn parse_bucket(headers: &HeaderMap, name: &str) -> Option<Bucket> {
+    let get = |field: &str| {
+        headers
+            .get(format!("anthropic-ratelimit-{}-{}", name, field))
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+    };
+
+    Some(Bucket {
+        limit: get("limit")?.parse().ok()?,
+        remaining: get("remaining")?.parse().ok()?,
+        reset: u64::try_from(time::parse_rfc3339(&get("reset")?)?).ok()?,
+    })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 64-bit FNV-1a, a hash that is the same on every build and platform
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Where the shared state lives: $XDG_STATE_HOME or ~/.local/state
+fn state_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("johnathan")
+}
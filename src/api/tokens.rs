@@ -0,0 +1,76 @@
+//! Offline Token Estimation
+//!
+//! Topic 14: Context Window Strategies
+//!
+//! The count_tokens endpoint gives exact numbers but costs a round trip.
+//! Before every request we only need a rough idea of how full the context
+//! window is, so we estimate locally: English text and JSON average about
+//! four characters per token for Claude's tokenizer. Images are the
+//! exception: they are billed by pixel area, not by the length of their
+//! base64 data, so each one counts as a fixed IMAGE_TOKENS.
+
+use super::client::{ContentBlock, Message, MessageContent, Tool, ToolResultContent};
+
+/// Average characters per token (rough rule of thumb)
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Fixed overhead the API adds per message (role markers, separators)
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Tokens for one image at the API's maximum size (about 1.15 megapixels / 750)
+const IMAGE_TOKENS: usize = 1600;
+
+/// Approximate token count for a piece of text
+pub fn estimate_text(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Approximate the input tokens of a full request: history + system + tools
+///
+/// Messages and tools are measured by their JSON form, which is close to
+/// what the API actually tokenizes.
+pub fn estimate_request(messages: &[Message], system_prompt: Option<&str>, tools: &[Tool]) -> usize {
+    let messages_tokens: usize = messages
+        .iter()
+        .map(|m| {
+            let json = serde_json::to_string(m).unwrap_or_default();
+            let (images, image_chars) = image_data(m);
+            (json.chars().count() - image_chars).div_ceil(CHARS_PER_TOKEN) + images * IMAGE_TOKENS + TOKENS_PER_MESSAGE
+        })
+        .sum();
+
+    let system_tokens = system_prompt.map(estimate_text).unwrap_or(0);
+
+    let tools_tokens: usize = tools
+        .iter()
+        .map(|t| estimate_text(&serde_json::to_string(t).unwrap_or_default()))
+        .sum();
+
+    messages_tokens + system_tokens + tools_tokens
+}
+
+/// Number of images in a message and the total length of their base64 data
+fn image_data(message: &Message) -> (usize, usize) {
+    let MessageContent::Blocks { content } = &message.content else {
+        return (0, 0);
+    };
+
+    let mut images = 0;
+    let mut chars = 0;
+    let mut count = |block: &ContentBlock| {
+        if let ContentBlock::Image { source } = block {
+            images += 1;
+            chars += source.data.len();
+        }
+    };
+    for block in content {
+        match block {
+            ContentBlock::ToolResult {
+                content: ToolResultContent::Blocks(inner),
+                ..
+            } => inner.iter().for_each(&mut count),
+            other => count(other),
+        }
+    }
+    (images, chars)
+}
@@ -0,0 +1,194 @@
+//! Context Window Management
+//!
+//! Topic 14: Context Window Strategies
+//!
+//! The API is stateless, so every turn resends the whole history. A long
+//! REPL session eventually outgrows the model's context window and the
+//! request fails with a 400. Before each request we project the size of
+//! history + system prompt + tools and react:
+//! - below WARN_RATIO of the budget: nothing to do
+//! - below COMPACT_RATIO: warn the user
+//! - above: compact by summarizing the oldest part of the conversation
+
+use crate::api::{self, ClientConfig, ContentBlock, Message, MessageContent, Tool};
+
+/// Fraction of the input budget at which we start warning
+const WARN_RATIO: f64 = 0.75;
+
+/// Fraction of the input budget at which we compact the history
+const COMPACT_RATIO: f64 = 0.90;
+
+/// Instructions for the summarization request used when compacting
+const SUMMARY_PROMPT: &str = "You summarize conversations between a user and a coding assistant. \
+Write a concise summary that preserves decisions, file names, code details, \
+open questions and anything the assistant promised to do. Output only the summary.";
+
+/// How full the context window is projected to be
+#[derive(Debug, PartialEq)]
+pub enum ContextStatus {
+    Ok,
+    Warn,
+    Compact,
+}
+
+/// Tokens available for input once the response has room to be generated
+pub fn input_budget(config: &ClientConfig) -> usize {
+    config.model.context_window.saturating_sub(config.max_tokens as usize)
+}
+
+/// Classify a projected input size against the budget
+pub fn status(config: &ClientConfig, projected: usize) -> ContextStatus {
+    let budget = input_budget(config) as f64;
+    let used = projected as f64;
+    if used >= budget * COMPACT_RATIO {
+        ContextStatus::Compact
+    } else if used >= budget * WARN_RATIO {
+        ContextStatus::Warn
+    } else {
+        ContextStatus::Ok
+    }
+}
+
+/// Check the projected request size and warn or compact before sending
+pub fn prepare(
+    history: &mut Vec<Message>,
+    config: &ClientConfig,
+    system_prompt: Option<&str>,
+    tools: &[Tool],
+    verbose: bool,
+) {
+    let projected = api::estimate_request(history, system_prompt, tools);
+
+    if verbose {
+        println!("[context: ~{} tokens, {}% of budget]", projected, percent(config, projected));
+    }
+
+    match status(config, projected) {
+        ContextStatus::Ok => {}
+        ContextStatus::Warn => {
+            println!(
+                "[warning: context is {}% full (~{} tokens); older turns will be summarized soon]",
+                percent(config, projected),
+                projected
+            );
+        }
+        ContextStatus::Compact => {
+            println!(
+                "[context is {}% full (~{} tokens), compacting history...]",
+                percent(config, projected),
+                projected
+            );
+            match compact(history, config) {
+                Ok(removed) => {
+                    let after = api::estimate_request(history, system_prompt, tools);
+                    println!("[compacted {} messages, now ~{} tokens]", removed, after);
+                }
+                Err(e) => println!("[could not compact history: {}]", e),
+            }
+        }
+    }
+}
+
+/// Print the current context usage (the /tokens REPL command)
+///
+/// Shows the offline estimate, then asks the API for the exact count.
+pub fn report(history: &[Message], config: &ClientConfig, system_prompt: Option<&str>, tools: &[Tool]) {
+    let estimate = api::estimate_request(history, system_prompt, tools);
+    println!(
+        "Context: ~{} tokens estimated ({}% of {} input budget, {} messages)",
+        estimate,
+        percent(config, estimate),
+        input_budget(config),
+        history.len()
+    );
+    println!(
+        "Context: resending it costs ~${:.4} on {}",
+        config.model.cost(estimate, 0),
+        config.model.id
+    );
+
+    // count_tokens rejects an empty message list
+    if history.is_empty() {
+        return;
+    }
+
+    match api::count_tokens(config, history, system_prompt, tools) {
+        Ok(exact) => println!("Context: {} tokens counted by the API", exact),
+        Err(e) => println!("Context: exact count unavailable ({})", e),
+    }
+}
+
+/// Replace the oldest part of the history with a summary of it
+///
+/// Returns how many messages were folded into the summary. The split point
+/// is a plain user message or, inside a long tool loop, the assistant
+/// message after a tool_result, so a tool_use is never separated from its
+/// tool_result and the history still starts with a user turn.
+pub fn compact(history: &mut Vec<Message>, config: &ClientConfig) -> Result<usize, String> {
+    let split = split_point(history).ok_or("no safe point to split the history")?;
+
+    let transcript: String = history[..split]
+        .iter()
+        .map(render)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let response = api::send_messages(
+        config,
+        vec![Message::user(&transcript)],
+        Some(SUMMARY_PROMPT),
+        Vec::new(),
+    )?;
+
+    let mut compacted = vec![Message::user(&format!(
+        "Summary of our earlier conversation:\n\n{}",
+        response.text
+    ))];
+    // Roles must keep alternating
+    if history[split].role == "user" {
+        compacted.push(Message::assistant("Understood. I'll continue from that summary."));
+    }
+    compacted.extend(history.drain(split..));
+    *history = compacted;
+
+    Ok(split)
+}
+
+/// Find the safe split point closest to the middle of the history: a plain
+/// user message, or an assistant message right after a user one (whose
+/// tool_results answer every tool_use before it)
+fn split_point(history: &[Message]) -> Option<usize> {
+    let middle = history.len() / 2;
+    (2..history.len())
+        .filter(|&i| {
+            is_user_text(&history[i]) || (history[i].role == "assistant" && history[i - 1].role == "user")
+        })
+        .min_by_key(|&i| i.abs_diff(middle))
+}
+
+fn is_user_text(message: &Message) -> bool {
+    message.role == "user" && matches!(message.content, MessageContent::Text { .. })
+}
+
+/// Render a message as plain text for the summarizer
+fn render(message: &Message) -> String {
+    let body = match &message.content {
+        MessageContent::Text { content } => content.clone(),
+        MessageContent::Blocks { content } => content
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text } => text.clone(),
+                ContentBlock::ToolUse { name, input, .. } => format!("[tool call {}: {}]", name, input),
+                ContentBlock::Image { source } => format!("[image: {}]", source.media_type),
+                ContentBlock::ToolResult { content, .. } => format!("[tool result: {}]", content.to_text()),
+                ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    format!("{}: {}", message.role, body)
+}
+
+fn percent(config: &ClientConfig, tokens: usize) -> usize {
+    tokens * 100 / input_budget(config).max(1)
+}
@@ -1,21 +1,36 @@
-/// Johnathan Agent - An AI Agent CLI
-///
-/// Topic 1: Agent = loop of observe -> think -> act
-/// Topic 2: The REPL pattern - Read, Eval, Print, Loop
-/// Topic 3: CLI interface - args, feedback, modes
-/// Topic 4: HTTP Requests and API Basics
-/// Topic 5: The Anthropic API - system prompts, message history
-/// Topic 6: Streaming Responses - real-time token display
-/// Topic 8: Tool Use / Function Calling
-/// Topic 9: Designing a Tool System
+//! Johnathan Agent - An AI Agent CLI
+//!
+//! Topic 1: Agent = loop of observe -> think -> act
+//! Topic 2: The REPL pattern - Read, Eval, Print, Loop
+//! Topic 3: CLI interface - args, feedback, modes
+//! Topic 4: HTTP Requests and API Basics
+//! Topic 5: The Anthropic API - system prompts, message history
+//! Topic 6: Streaming Responses - real-time token display
+//! Topic 8: Tool Use / Function Calling
+//! Topic 9: Designing a Tool System
+//! Topic 10: Implementing Core Tools
+//! Topic 11: The Tool Use Loop
+//! Topic 14: Context Window Strategies
+//! Topic 19: Multi-turn Planning
+//! Topic 20: Conversation Persistence
 
 mod api;
+mod context;
+mod session;
+mod time;
 mod tools;
 
-use api::Message;
+use api::{ChatResponse, ClientConfig, ContentBlock, Message, ModelCatalog, RateLimiter};
 use clap::Parser;
+use session::Session;
 use std::io::{self, Write};
-use tools::{GetTimeTool, ToolRegistry};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tools::{
+    ApplyPatchTool, Approval, BashTool, CargoTool, Decision, EditFileTool, FileTracker, GetTimeTool, GitTool,
+    GlobTool, GrepTool, ListDirectoryTool, Permissions, PluginTool, ReadFileTool, Rule, Sandbox, Spill, TodoList, TodoTool, ToolOutput,
+    ToolExecutor, ToolRegistry, Workspace, WriteFileTool,
+};
 
 /// System prompt defines the agent's persona and behavior
 const SYSTEM_PROMPT: &str = r#"You are Johnathan, an AI coding assistant.
@@ -23,7 +38,11 @@ const SYSTEM_PROMPT: &str = r#"You are Johnathan, an AI coding assistant.
 You help users with programming tasks. Be concise and direct.
 When asked to perform tasks, explain what you're doing briefly.
 
-You are running as a CLI agent and can have multi-turn conversations."#;
+You are running as a CLI agent and can have multi-turn conversations.
+Use your tools to look at the user's code instead of guessing what it contains."#;
+
+/// Maximum model round trips in one turn, so a tool loop can't run forever
+const MAX_TOOL_ROUNDS: usize = 25;
 
 /// An AI agent that can perform tasks
 #[derive(Parser)]
@@ -36,6 +55,98 @@ struct Cli {
     /// Print verbose output
     #[arg(short, long)]
     verbose: bool,
+
+    /// Model id or alias (e.g. "sonnet", "claude-opus-4-1")
+    #[arg(short, long, default_value = api::DEFAULT_MODEL)]
+    model: String,
+
+    /// Maximum tokens per response
+    #[arg(long, default_value_t = api::MAX_TOKENS)]
+    max_tokens: u32,
+
+    /// Enable extended thinking with this token budget
+    #[arg(long, value_name = "BUDGET")]
+    thinking: Option<u32>,
+
+    /// Model to fall back to when the previous one stays overloaded (repeatable)
+    #[arg(long = "fallback-model", value_name = "MODEL")]
+    fallback_models: Vec<String>,
+
+    /// Enable an API beta feature via the anthropic-beta header (repeatable)
+    #[arg(long = "beta", value_name = "NAME")]
+    betas: Vec<String>,
+
+    /// Extra HTTP header as "Name: value" (repeatable)
+    #[arg(long = "header", value_name = "HEADER")]
+    headers: Vec<String>,
+
+    /// JSON object merged into every message request body
+    #[arg(long, value_name = "JSON")]
+    extra_body: Option<String>,
+
+    /// Glob to hide from directory listings, on top of .gitignore (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    ignore: Vec<String>,
+
+    /// Refresh the model catalog from the API before starting
+    #[arg(long)]
+    fetch_models: bool,
+
+    /// Resume a session saved with /save
+    #[arg(long, value_name = "FILE")]
+    resume: Option<PathBuf>,
+
+    /// Let matching tool calls run without asking, e.g. "bash(cargo test*)" (repeatable)
+    #[arg(long, value_name = "RULE")]
+    allow: Vec<String>,
+
+    /// Always ask before matching tool calls, e.g. "write_file" (repeatable)
+    #[arg(long, value_name = "RULE")]
+    ask: Vec<String>,
+
+    /// Refuse matching tool calls, e.g. "bash(rm *)" (repeatable)
+    #[arg(long, value_name = "RULE")]
+    deny: Vec<String>,
+
+    /// With a prompt argument: approve tool calls that would ask (deny rules still apply)
+    #[arg(long)]
+    yes: bool,
+
+    /// Read-only tool calls run at the same time, at most (1: one by one)
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    parallel_tools: usize,
+
+    /// Characters a tool result may have before it is cut to an excerpt
+    /// (the full text is saved to a file the agent can page through)
+    #[arg(long, value_name = "CHARS", default_value_t = 40_000)]
+    output_budget: usize,
+
+    /// Another directory the file tools may use, besides the current one (repeatable)
+    #[arg(long, value_name = "DIR")]
+    add_dir: Vec<PathBuf>,
+
+    /// Let the file tools use a file or directory that looks secret, e.g. ".env" (repeatable)
+    #[arg(long, value_name = "PATH")]
+    expose: Vec<PathBuf>,
+
+    /// Run shell commands, cargo, git and plugins in a sandbox: writes only to
+    /// the workspace, --add-dir directories and temp dir, no network,
+    /// CPU/memory limits (Linux with Landlock)
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Let sandboxed commands use the network
+    #[arg(long, requires = "sandbox")]
+    sandbox_network: bool,
+
+    /// Another directory sandboxed commands may write to, e.g. ~/.cargo (repeatable)
+    #[arg(long, value_name = "DIR", requires = "sandbox")]
+    sandbox_write: Vec<PathBuf>,
+
+    /// A JSON file declaring external tools (repeatable); the file in
+    /// ~/.config/johnathan/plugins.json is always loaded if it exists
+    #[arg(long, value_name = "FILE")]
+    plugins: Vec<PathBuf>,
 }
 
 fn main() {
@@ -54,13 +165,210 @@ fn main() {
         }
     };
 
+    let mut extra_headers = Vec::new();
+    for header in &cli.headers {
+        match header.split_once(':') {
+            Some((name, value)) => extra_headers.push((name.trim().to_string(), value.trim().to_string())),
+            None => {
+                eprintln!("Error: header '{}' must look like 'Name: value'", header);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let extra_body = match cli.extra_body.as_deref().map(serde_json::from_str) {
+        None => serde_json::Map::new(),
+        Some(Ok(serde_json::Value::Object(fields))) => fields,
+        Some(_) => {
+            eprintln!("Error: --extra-body must be a JSON object");
+            std::process::exit(1);
+        }
+    };
+
+    // The model is filled in once the catalog is complete; fetching the
+    // catalog already needs the key and headers
+    let mut catalog = ModelCatalog::builtin();
+    let mut config = ClientConfig {
+        rate_limiter: RateLimiter::for_key(&api_key),
+        api_key,
+        model: catalog.models()[0].clone(),
+        max_tokens: cli.max_tokens,
+        thinking_budget: cli.thinking,
+        fallback_models: Vec::new(),
+        betas: cli.betas,
+        extra_headers,
+        extra_body,
+    };
+
+    // Resolve the requested model against the catalog
+    if cli.fetch_models {
+        match catalog.refresh(&config) {
+            Ok(added) if cli.verbose => println!("[model catalog: {} new models fetched]", added),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: could not fetch model list: {}", e),
+        }
+    }
+
+    config.model = match catalog.resolve(&cli.model) {
+        Some(model) => model.clone(),
+        None => {
+            eprintln!("Error: unknown model '{}'", cli.model);
+            let known: Vec<&str> = catalog.models().iter().map(|m| m.id.as_str()).collect();
+            eprintln!("Known models: {}", known.join(", "));
+            std::process::exit(1);
+        }
+    };
+
+    for name in &cli.fallback_models {
+        match catalog.resolve(name) {
+            Some(model) => config.fallback_models.push(model.clone()),
+            None => {
+                eprintln!("Error: unknown fallback model '{}'", name);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(e) = config.validate() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
     // Set up the tool registry
+    // File tools share a record of what has been read this session, and
+    // stay inside the workspace
+    let files = FileTracker::new();
+    // Oversized results are saved to files that read_file must be able to reach
+    let spill = match Spill::new(cli.output_budget) {
+        Ok(spill) => spill,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut add_dirs = cli.add_dir.clone();
+    add_dirs.push(spill.dir().to_path_buf());
+    let workspace = match workspace(&add_dirs, &cli.expose) {
+        Ok(workspace) => workspace,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     let mut registry = ToolRegistry::new();
     registry.register(GetTimeTool::new());
+    registry.register(ReadFileTool::new(files.clone(), workspace.clone()));
+    registry.register(EditFileTool::new(files.clone(), workspace.clone()));
+    registry.register(WriteFileTool::new(files.clone(), workspace.clone()));
+    registry.register(ApplyPatchTool::new(files, workspace.clone()));
+    // Everything a tool starts (shell commands, cargo, git, plugins) runs
+    // in the same sandbox
+    let sandbox = if cli.sandbox {
+        let mut writable: Vec<PathBuf> = std::env::current_dir().into_iter().collect();
+        writable.push(std::env::temp_dir());
+        writable.extend(cli.add_dir.iter().cloned());
+        writable.extend(cli.sandbox_write.iter().cloned());
+        match Sandbox::new(writable, cli.sandbox_network) {
+            Ok(sandbox) => {
+                if cli.verbose {
+                    println!("[sandbox: {}]", sandbox.describe());
+                }
+                Some(sandbox)
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let mut bash = match BashTool::new() {
+        Ok(bash) => bash,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut cargo = CargoTool::new();
+    let mut git = GitTool::new(workspace.clone());
+    if let Some(sandbox) = &sandbox {
+        bash = bash.with_sandbox(sandbox.clone());
+        cargo = cargo.with_sandbox(sandbox.clone());
+        git = git.with_sandbox(sandbox.clone());
+    }
+    registry.register(bash);
+    registry.register(cargo);
+    registry.register(git);
+    registry.register(GrepTool::new(workspace.clone()));
+    registry.register(GlobTool::new(workspace.clone()));
+    registry.register(ListDirectoryTool::new(cli.ignore.clone(), workspace));
+
+    // The task list belongs to the session, so it is saved along with it
+    let todos = TodoList::new();
+    registry.register(TodoTool::new(todos.clone()));
+
+    // External tools; a plugin may not replace a built-in tool or another plugin
+    let plugin_files = PluginTool::user_config().into_iter().chain(cli.plugins.iter().cloned());
+    for path in plugin_files {
+        let plugins = match PluginTool::load(&path) {
+            Ok(plugins) => plugins,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for mut plugin in plugins {
+            if let Some(sandbox) = &sandbox {
+                plugin = plugin.with_sandbox(sandbox.clone());
+            }
+            if registry.definitions().iter().any(|tool| tool.name == plugin.name()) {
+                eprintln!("Error: {}: a tool named '{}' already exists", path.display(), plugin.name());
+                std::process::exit(1);
+            }
+            println!("[plugin: {} from {}]", plugin.name(), path.display());
+            registry.register(plugin);
+        }
+    }
+
+    // Permission rules; updating the todo list only touches session state
+    let mut rules = Vec::new();
+    let rule_args = std::iter::once((Decision::Allow, "todo_write"))
+        .chain(cli.allow.iter().map(|r| (Decision::Allow, r.as_str())))
+        .chain(cli.ask.iter().map(|r| (Decision::Ask, r.as_str())))
+        .chain(cli.deny.iter().map(|r| (Decision::Deny, r.as_str())));
+    for (decision, text) in rule_args {
+        match Rule::parse(text) {
+            Ok(rule) => rules.push((rule, decision)),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    // Only the REPL has someone to ask; a single prompt refuses instead, or
+    // approves with --yes
+    let mut permissions = Permissions::new(rules);
+    if cli.prompt.is_none() {
+        permissions = permissions.with_prompt(Arc::new(ask_permission));
+    } else if cli.yes {
+        permissions = permissions.approve_unattended();
+    }
+    registry.set_permissions(permissions);
+    registry.set_max_parallel(cli.parallel_tools);
+    registry.set_spill(spill);
 
     if cli.verbose {
         println!("[verbose mode enabled]");
         println!("[API key loaded]");
+        println!("[model: {}]", config.model.id);
+        for fallback in &config.fallback_models {
+            println!("[fallback model: {}]", fallback.id);
+        }
+        if !config.betas.is_empty() {
+            println!("[betas: {}]", config.betas.join(","));
+        }
         println!("[System prompt: {} chars]", SYSTEM_PROMPT.len());
         println!("[tools registered: {}]\n", registry.definitions().len());
     }
@@ -68,32 +376,63 @@ fn main() {
     // Two modes: interactive (REPL) or non-interactive (single prompt)
     match cli.prompt {
         Some(prompt) => {
-            run_once(&prompt, &api_key, &registry, cli.verbose);
+            run_once(&prompt, &config, &registry, cli.verbose);
         }
         None => {
-            run_repl(&api_key, &registry, cli.verbose);
+            let mut history = Vec::new();
+            if let Some(path) = &cli.resume {
+                match Session::load(path) {
+                    Ok(session) => {
+                        println!("[resumed {} messages from {}]", session.messages.len(), path.display());
+                        history = session.messages;
+                        todos.replace(session.todos);
+                        if !todos.items().is_empty() {
+                            println!("{}", todos.render());
+                        }
+                        println!();
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            run_repl(&config, &registry, &todos, history, cli.verbose);
         }
     }
 }
 
+/// The workspace for the file tools: the current directory plus the
+/// --add-dir directories, with the --expose paths let through
+fn workspace(add_dirs: &[PathBuf], exposed: &[PathBuf]) -> Result<Workspace, String> {
+    let cwd = std::env::current_dir().map_err(|e| format!("Cannot read the current directory: {}", e))?;
+    let mut workspace = Workspace::new(&cwd)?;
+    for dir in add_dirs {
+        workspace = workspace.add_dir(dir)?;
+    }
+    for path in exposed {
+        workspace = workspace.expose(path)?;
+    }
+    Ok(workspace)
+}
+
 /// Non-interactive mode: process a single prompt and exit
-fn run_once(prompt: &str, api_key: &str, registry: &ToolRegistry, verbose: bool) {
+fn run_once(prompt: &str, config: &ClientConfig, registry: &ToolRegistry, verbose: bool) {
     if verbose {
         println!("[non-interactive mode]");
         println!("[prompt: {}]\n", prompt);
     }
 
-    let messages = vec![Message::user(prompt)];
-    let response = eval_streaming(messages, api_key, registry, verbose);
+    let mut messages = vec![Message::user(prompt)];
+    let response = run_turn(&mut messages, config, registry, verbose);
     // Response already printed via streaming, just add newline
     println!("\n{}", if verbose { format!("[done: {} chars]", response.len()) } else { String::new() });
 }
 
 /// Interactive mode: the REPL with conversation history
-fn run_repl(api_key: &str, registry: &ToolRegistry, verbose: bool) {
-    println!("Type 'quit' or 'exit' to stop.\n");
-
-    let mut history: Vec<Message> = Vec::new();
+fn run_repl(config: &ClientConfig, registry: &ToolRegistry, todos: &TodoList, mut history: Vec<Message>, verbose: bool) {
+    println!("Type 'quit' or 'exit' to stop, '/tokens' for context usage, '/model' for model info,");
+    println!("'/todos' for the task list, '/save <file>' and '/load <file>' for sessions.\n");
 
     loop {
         let input = match read_input() {
@@ -106,17 +445,56 @@ fn run_repl(api_key: &str, registry: &ToolRegistry, verbose: bool) {
             break;
         }
 
+        if input == "/tokens" {
+            context::report(&history, config, Some(SYSTEM_PROMPT), &registry.definitions());
+            println!();
+            continue;
+        }
+
+        if input == "/model" {
+            println!("{}\n", config.model.describe());
+            continue;
+        }
+
+        if input == "/todos" {
+            println!("{}\n", todos.render());
+            continue;
+        }
+
+        if let Some(path) = input.strip_prefix("/save ") {
+            let session = Session {
+                model: config.model.id.clone(),
+                messages: history.clone(),
+                todos: todos.items(),
+            };
+            match session.save(Path::new(path.trim())) {
+                Ok(()) => println!("[saved {} messages to {}]\n", history.len(), path.trim()),
+                Err(e) => eprintln!("Error: {}\n", e),
+            }
+            continue;
+        }
+
+        if let Some(path) = input.strip_prefix("/load ") {
+            match Session::load(Path::new(path.trim())) {
+                Ok(session) => {
+                    history = session.messages;
+                    todos.replace(session.todos);
+                    println!("[loaded {} messages from {}]", history.len(), path.trim());
+                    println!("{}\n", todos.render());
+                }
+                Err(e) => eprintln!("Error: {}\n", e),
+            }
+            continue;
+        }
+
         history.push(Message::user(&input));
 
         if verbose {
             println!("[history: {} messages]", history.len());
         }
 
-        // Get streaming response
-        let response = eval_streaming(history.clone(), api_key, registry, verbose);
-
-        // Add assistant response to history
-        history.push(Message::assistant(&response));
+        // Let the model respond, using tools as needed (adds to history)
+        run_turn(&mut history, config, registry, verbose);
 
         // Newline after streamed response
         println!("\n");
@@ -138,13 +516,104 @@ fn read_input() -> Option<String> {
     }
 }
 
+/// Ask the user on the terminal whether a tool call may run (anything but
+/// y/yes or a/always is no)
+fn ask_permission(call: &str, grant: &str) -> Approval {
+    println!("\n[permission] {}", call);
+    print!("Allow? [y]es / [n]o / [a]lways allow {} ", grant);
+    io::stdout().flush().ok();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return Approval::No;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Approval::Yes,
+        "a" | "always" => Approval::Always,
+        _ => Approval::No,
+    }
+}
+
 fn should_exit(input: &str) -> bool {
     let lower = input.to_lowercase();
     lower == "quit" || lower == "exit" || lower == "q"
 }
 
+/// One turn of the agent: call the model, run the tools it asks for, send
+/// the results back, and repeat until it answers without using a tool
+///
+/// Every message of the exchange is appended to `history`. Returns the text
+/// of the final answer.
+fn run_turn(history: &mut Vec<Message>, config: &ClientConfig, registry: &ToolRegistry, verbose: bool) -> String {
+    for _ in 0..MAX_TOOL_ROUNDS {
+        // Keep every request inside the context window; a long tool loop
+        // grows the history as fast as new user input does
+        context::prepare(history, config, Some(SYSTEM_PROMPT), &registry.definitions(), verbose);
+
+        let response = match eval_streaming(history.clone(), config, registry, verbose) {
+            Ok(response) => response,
+            Err(msg) => {
+                history.push(Message::assistant(&msg));
+                return msg;
+            }
+        };
+
+        if !response.has_tool_calls() {
+            history.push(Message::assistant(&response.text));
+            return response.text;
+        }
+
+        // ACT: run the requested tools (read-only ones in parallel) and
+        // collect results for the model, in the order they were asked for
+        history.push(Message::assistant_response(&response));
+
+        for call in &response.tool_calls {
+            println!("\n[tool: {} {}]", call.name, call.input);
+        }
+        let runnable = response
+            .tool_calls
+            .iter()
+            .filter(|call| call.input_error.is_none())
+            .map(|call| (call.name.clone(), call.input.clone()))
+            .collect();
+        let mut outputs = registry.execute_all(runnable).into_iter();
+
+        let results: Vec<ContentBlock> = response
+            .tool_calls
+            .iter()
+            .map(|call| {
+                let output = match &call.input_error {
+                    Some(error) => ToolOutput::error(error.clone()),
+                    None => outputs.next().unwrap_or_else(|| ToolOutput::error("The call did not run")),
+                };
+                if output.is_error {
+                    println!("[tool error: {}: {}]", call.name, output.text_content());
+                } else if registry.echoes_output(&call.name) {
+                    println!("{}", output.text_content());
+                }
+                if verbose {
+                    println!("[tool result: {}: {}]", call.name, output.metadata.describe());
+                }
+                output.into_result(call.id.clone())
+            })
+            .collect();
+
+        history.push(Message::tool_results(results));
+    }
+
+    let msg = format!("Stopped after {} tool rounds without a final answer.", MAX_TOOL_ROUNDS);
+    print!("{}", msg);
+    history.push(Message::assistant(&msg));
+    msg
+}
+
 /// EVAL with streaming: prints tokens as they arrive
-fn eval_streaming(messages: Vec<Message>, api_key: &str, registry: &ToolRegistry, verbose: bool) -> String {
+fn eval_streaming(
+    messages: Vec<Message>,
+    config: &ClientConfig,
+    registry: &ToolRegistry,
+    verbose: bool,
+) -> Result<ChatResponse, String> {
     // Show thinking indicator
     print!("Thinking...");
     io::stdout().flush().ok();
@@ -156,7 +625,7 @@ fn eval_streaming(messages: Vec<Message>, api_key: &str, registry: &ToolRegistry
 
     // Stream response, printing each chunk as it arrives
     let result = api::send_messages_streaming(
-        api_key,
+        config,
         messages,
         Some(SYSTEM_PROMPT),
         tools,
@@ -175,10 +644,17 @@ fn eval_streaming(messages: Vec<Message>, api_key: &str, registry: &ToolRegistry
 
     match result {
         Ok(response) => {
+            // Make a fallback visible in the transcript
+            if response.model != config.model.id {
+                print!(" [answered by {}]", response.model);
+            }
             if verbose {
+                if !response.thinking.is_empty() {
+                    print!(" [thinking blocks: {}]", response.thinking.len());
+                }
                 print!(" [stop: {}]", response.stop_reason);
             }
-            response.text
+            Ok(response)
         }
         Err(e) => {
             // Clear thinking indicator on error
@@ -187,7 +663,7 @@ fn eval_streaming(messages: Vec<Message>, api_key: &str, registry: &ToolRegistry
             }
             let msg = format!("Error: {}", e);
             print!("{}", msg);
-            msg
+            Err(msg)
         }
     }
 }
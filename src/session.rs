@@ -0,0 +1,40 @@
+//! Saved Sessions
+//!
+//! Topic 20: Conversation Persistence
+//!
+//! A session is everything needed to pick up where we left off: the
+//! message history and the todo list. It is saved as plain JSON (the same
+//! shape the API uses for messages), so a session file is also easy to
+//! inspect by hand.
+
+use crate::api::Message;
+use crate::tools::TodoItem;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    /// Model the session was saved with (informational; resuming uses --model)
+    pub model: String,
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub todos: Vec<TodoItem>,
+}
+
+impl Session {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        // Write then rename, so a crash never leaves half a session behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| format!("{} is not a valid session file: {}", path.display(), e))
+    }
+}
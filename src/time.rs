@@ -0,0 +1,739 @@
+//! Dates, Times and Time Zones
+//!
+//! Calendar math and time zone support without an external crate: enough
+//! to print ISO 8601 timestamps, parse the ones the model or the API send
+//! us, and convert between zones.
+//!
+//! Time zones come from the system's zoneinfo database (TZif files, see
+//! `man tzfile`). A TZif file lists every historical offset change; for
+//! times after the last listed change it ends with a POSIX TZ rule such as
+//! `EST5EDT,M3.2.0,M11.1.0` ("second Sunday of March to first Sunday of
+//! November"), which we evaluate ourselves.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+// ============================================================================
+// Civil Calendar
+// ============================================================================
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm)
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of `days_from_civil`: (year, month, day)
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// 0 = Sunday ... 6 = Saturday
+pub fn weekday(days: i64) -> i64 {
+    (days + 4).rem_euclid(7)
+}
+
+pub fn weekday_name(days: i64) -> &'static str {
+    ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"][weekday(days) as usize]
+}
+
+pub fn days_in_month(year: i64, month: i64) -> i64 {
+    days_from_civil(year + (month / 12), month % 12 + 1, 1) - days_from_civil(year, month, 1)
+}
+
+/// Shift a date by whole months, clamping the day (Jan 31 + 1 month = Feb 28/29)
+pub fn add_months(year: i64, month: i64, day: i64, months: i64) -> (i64, i64, i64) {
+    let index = year * 12 + (month - 1) + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) + 1);
+    (year, month, day.min(days_in_month(year, month)))
+}
+
+// ============================================================================
+// ISO 8601 / RFC 3339
+// ============================================================================
+
+/// A parsed timestamp: local wall-clock seconds plus the offset, if given
+pub struct ParsedTime {
+    /// Seconds since the epoch of the wall-clock reading, as if it were UTC
+    pub local_secs: i64,
+    /// Offset east of UTC in seconds, when the text carried one ("Z", "+02:00")
+    pub offset: Option<i64>,
+}
+
+/// Parse "2026-01-15", "2026-01-15T10:30", "2026-01-15 10:30:00.5Z", "...+02:00"
+pub fn parse_iso8601(s: &str) -> Option<ParsedTime> {
+    let s = s.trim();
+    let (date, time) = match s.find(['T', 't', ' ']) {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, ""),
+    };
+
+    let mut date_parts = date.split('-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+    if date_parts.next().is_some() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    // Split the clock time from the offset ("Z", "+02:00", "-05:00", "+0200")
+    let (clock, offset_text) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => (time, ""),
+    };
+
+    let mut clock_secs = 0;
+    if !clock.is_empty() {
+        let mut parts = clock.split(':');
+        let hour: i64 = parts.next()?.parse().ok()?;
+        let minute: i64 = parts.next()?.parse().ok()?;
+        let second: f64 = parts.next().map(|s| s.parse().ok()).unwrap_or(Some(0.0))?;
+        if hour > 24 || minute > 59 || !(0.0..61.0).contains(&second) {
+            return None;
+        }
+        clock_secs = hour * 3600 + minute * 60 + second as i64;
+    }
+
+    let offset = match offset_text {
+        "" => None,
+        "Z" | "z" => Some(0),
+        _ => {
+            let sign = if offset_text.starts_with('-') { -1 } else { 1 };
+            let digits: String = offset_text[1..].chars().filter(|c| *c != ':').collect();
+            if digits.len() != 4 && digits.len() != 2 {
+                return None;
+            }
+            let hours: i64 = digits.get(..2)?.parse().ok()?;
+            let minutes: i64 = digits.get(2..).filter(|m| !m.is_empty()).map_or(Some(0), |m| m.parse().ok())?;
+            Some(sign * (hours * 3600 + minutes * 60))
+        }
+    };
+
+    Some(ParsedTime {
+        local_secs: days_from_civil(year, month, day) * SECS_PER_DAY + clock_secs,
+        offset,
+    })
+}
+
+/// Parse an RFC 3339 timestamp that must carry an offset into Unix seconds
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    let parsed = parse_iso8601(s)?;
+    Some(parsed.local_secs - parsed.offset?)
+}
+
+/// Format Unix seconds at the given offset: "2026-01-15T10:30:00+01:00"
+pub fn format_iso8601(utc_secs: i64, offset: i64) -> String {
+    let local = utc_secs + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(SECS_PER_DAY));
+    let secs = local.rem_euclid(SECS_PER_DAY);
+    let zone = if offset == 0 {
+        "Z".to_string()
+    } else {
+        format_offset(offset)
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        zone
+    )
+}
+
+/// "+05:30" / "-04:00"
+pub fn format_offset(offset: i64) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let abs = offset.abs();
+    format!("{}{:02}:{:02}", sign, abs / 3600, abs % 3600 / 60)
+}
+
+// ============================================================================
+// Time Zones
+// ============================================================================
+
+/// The offset in effect at some instant
+#[derive(Debug, Clone)]
+pub struct LocalOffset {
+    /// Seconds east of UTC
+    pub offset: i64,
+    pub is_dst: bool,
+    /// Abbreviation such as "CET" or "EDT"
+    pub abbreviation: String,
+}
+
+/// A time zone loaded from the zoneinfo database
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    /// IANA name ("Europe/Berlin"), or a description for unnamed zones
+    pub name: String,
+    /// Instants (Unix seconds) at which the offset changes
+    transitions: Vec<i64>,
+    /// For each transition, an index into `types`
+    transition_types: Vec<usize>,
+    types: Vec<LocalOffset>,
+    /// Rule for instants after the last transition
+    rule: Option<PosixTz>,
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self::from_rule("UTC", PosixTz::fixed("UTC", 0))
+    }
+
+    fn from_rule(name: &str, rule: PosixTz) -> Self {
+        Self {
+            name: name.to_string(),
+            transitions: Vec::new(),
+            transition_types: Vec::new(),
+            types: Vec::new(),
+            rule: Some(rule),
+        }
+    }
+
+    /// Load an IANA zone ("America/New_York") from the zoneinfo database
+    pub fn named(name: &str) -> Result<Self, String> {
+        if matches!(name, "UTC" | "Etc/UTC" | "Z" | "GMT") {
+            return Ok(Self::utc());
+        }
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            return Err(format!("Invalid time zone name: {}", name));
+        }
+
+        let path = zoneinfo_dir().join(name);
+        let bytes = fs::read(&path).map_err(|_| format!("Unknown time zone: {}", name))?;
+        parse_tzif(name, &bytes).ok_or_else(|| format!("Could not read time zone data for {}", name))
+    }
+
+    /// The system's zone: $TZ, else /etc/localtime, else UTC
+    pub fn local() -> Self {
+        if let Ok(tz) = std::env::var("TZ") {
+            let tz = tz.trim_start_matches(':');
+            if let Ok(zone) = Self::named(tz) {
+                return zone;
+            }
+            if let Some(rule) = PosixTz::parse(tz) {
+                return Self::from_rule(tz, rule);
+            }
+        }
+
+        let localtime = Path::new("/etc/localtime");
+        if let Ok(bytes) = fs::read(localtime) {
+            // The symlink target usually reveals the IANA name
+            let name = fs::read_link(localtime)
+                .ok()
+                .and_then(|target| {
+                    let target = target.to_string_lossy().into_owned();
+                    target.split_once("zoneinfo/").map(|(_, name)| name.to_string())
+                })
+                .unwrap_or_else(|| "localtime".to_string());
+            if let Some(zone) = parse_tzif(&name, &bytes) {
+                return zone;
+            }
+        }
+
+        Self::utc()
+    }
+
+    /// Offset in effect at the given instant
+    pub fn offset_at(&self, utc_secs: i64) -> LocalOffset {
+        let after_last = self.transitions.last().is_none_or(|&last| utc_secs >= last);
+        if after_last && let Some(rule) = &self.rule {
+            return rule.offset_at(utc_secs);
+        }
+
+        match self.transitions.partition_point(|&t| t <= utc_secs) {
+            // Before the first transition: the first standard-time type
+            0 => self
+                .types
+                .iter()
+                .find(|t| !t.is_dst)
+                .or(self.types.first())
+                .cloned()
+                .unwrap_or_else(|| PosixTz::fixed("UTC", 0).offset_at(0)),
+            n => self.types[self.transition_types[n - 1]].clone(),
+        }
+    }
+
+    /// Convert a wall-clock reading in this zone to Unix seconds
+    ///
+    /// Ambiguous times (when clocks fall back) resolve to the first
+    /// occurrence; times skipped by a spring-forward gap move past it.
+    pub fn to_utc(&self, local_secs: i64) -> i64 {
+        // The offsets in effect a day either side; a transition near this
+        // time lies between them
+        let candidates = [-SECS_PER_DAY, SECS_PER_DAY].map(|shift| {
+            let offset = self.offset_at(local_secs + shift).offset;
+            let utc = local_secs - offset;
+            (utc, self.offset_at(utc).offset == offset)
+        });
+        match candidates {
+            // Both readings exist (clocks fell back): the first
+            [(a, true), (b, true)] => a.min(b),
+            [(a, true), _] | [_, (a, true)] => a,
+            // Skipped by a gap: the earlier offset, which lands just past it
+            // (02:30 on a spring-forward night becomes 03:30)
+            [(a, false), (b, false)] => a.max(b),
+        }
+    }
+}
+
+/// Where the zoneinfo database lives ($TZDIR or /usr/share/zoneinfo)
+fn zoneinfo_dir() -> PathBuf {
+    std::env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"))
+}
+
+/// Parse a TZif file (versions 1-4)
+fn parse_tzif(name: &str, bytes: &[u8]) -> Option<TimeZone> {
+    let header = TzifHeader::parse(bytes)?;
+
+    // Version 2+ repeats the data with 64-bit times after the v1 block
+    let (header, data, time_size) = if header.version >= b'2' {
+        let second = bytes.get(44 + header.data_len(4)..)?;
+        (TzifHeader::parse(second)?, &second[44..], 8)
+    } else {
+        (header, &bytes[44..], 4)
+    };
+
+    let mut cursor = 0;
+    let mut take = |n: usize| -> Option<&[u8]> {
+        let slice = data.get(cursor..cursor + n)?;
+        cursor += n;
+        Some(slice)
+    };
+
+    let times = take(header.timecnt * time_size)?;
+    let transitions: Vec<i64> = times
+        .chunks(time_size)
+        .map(|c| match time_size {
+            8 => i64::from_be_bytes(c.try_into().unwrap_or_default()),
+            _ => i64::from(i32::from_be_bytes(c.try_into().unwrap_or_default())),
+        })
+        .collect();
+    let transition_types: Vec<usize> = take(header.timecnt)?.iter().map(|&i| i as usize).collect();
+
+    let raw_types = take(header.typecnt * 6)?.to_vec();
+    let abbreviations = take(header.charcnt)?.to_vec();
+    take(header.leapcnt * (time_size + 4))?;
+    take(header.isstdcnt)?;
+    take(header.isutcnt)?;
+    let footer_start = cursor;
+
+    let types: Vec<LocalOffset> = raw_types
+        .chunks(6)
+        .map(|t| {
+            let offset = i64::from(i32::from_be_bytes([t[0], t[1], t[2], t[3]]));
+            let start = t[5] as usize;
+            let end = abbreviations[start.min(abbreviations.len())..]
+                .iter()
+                .position(|&b| b == 0)
+                .map_or(abbreviations.len(), |p| start + p);
+            LocalOffset {
+                offset,
+                is_dst: t[4] != 0,
+                abbreviation: String::from_utf8_lossy(abbreviations.get(start..end).unwrap_or_default()).into_owned(),
+            }
+        })
+        .collect();
+
+    if transition_types.iter().any(|&i| i >= types.len()) {
+        return None;
+    }
+
+    // Footer: "\n<POSIX TZ string>\n"
+    let rule = if time_size == 8 {
+        std::str::from_utf8(data.get(footer_start..)?)
+            .ok()
+            .map(|f| f.trim_matches('\n'))
+            .and_then(PosixTz::parse)
+    } else {
+        None
+    };
+
+    Some(TimeZone {
+        name: name.to_string(),
+        transitions,
+        transition_types,
+        types,
+        rule,
+    })
+}
+
+struct TzifHeader {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl TzifHeader {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 44 || &bytes[..4] != b"TZif" {
+            return None;
+        }
+        let count = |i: usize| u32::from_be_bytes([bytes[20 + i * 4], bytes[21 + i * 4], bytes[22 + i * 4], bytes[23 + i * 4]]) as usize;
+        Some(Self {
+            version: bytes[4],
+            isutcnt: count(0),
+            isstdcnt: count(1),
+            leapcnt: count(2),
+            timecnt: count(3),
+            typecnt: count(4),
+            charcnt: count(5),
+        })
+    }
+
+    /// Size of the data block that follows the header
+    fn data_len(&self, time_size: usize) -> usize {
+        self.timecnt * time_size
+            + self.timecnt
+            + self.typecnt * 6
+            + self.charcnt
+            + self.leapcnt * (time_size + 4)
+            + self.isstdcnt
+            + self.isutcnt
+    }
+}
+
+// ============================================================================
+// POSIX TZ Rules
+// ============================================================================
+
+/// A POSIX TZ string such as "CET-1CEST,M3.5.0,M10.5.0/3"
+#[derive(Debug, Clone)]
+struct PosixTz {
+    std_abbr: String,
+    /// Seconds east of UTC (POSIX writes them west, so the sign is flipped)
+    std_offset: i64,
+    dst: Option<PosixDst>,
+}
+
+#[derive(Debug, Clone)]
+struct PosixDst {
+    abbr: String,
+    offset: i64,
+    start: DstDate,
+    /// Seconds after local midnight (standard time) when DST starts
+    start_time: i64,
+    end: DstDate,
+    /// Seconds after local midnight (daylight time) when DST ends
+    end_time: i64,
+}
+
+/// The day a DST period starts or ends
+#[derive(Debug, Clone)]
+enum DstDate {
+    /// Mm.w.d: weekday d (0 = Sunday) of week w (5 = last) of month m
+    MonthWeekDay(i64, i64, i64),
+    /// Jn: day n of the year, 1-365, never counting February 29
+    Julian(i64),
+    /// n: zero-based day of the year, counting February 29
+    ZeroBased(i64),
+}
+
+impl DstDate {
+    /// Days since the epoch of this date in `year`
+    fn day_in(&self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        match *self {
+            DstDate::Julian(n) => {
+                let leap = days_in_month(year, 2) == 29;
+                jan1 + n - 1 + i64::from(leap && n >= 60)
+            }
+            DstDate::ZeroBased(n) => jan1 + n,
+            DstDate::MonthWeekDay(month, week, dow) => {
+                let first = days_from_civil(year, month, 1);
+                let first_match = first + (dow - weekday(first)).rem_euclid(7);
+                let mut day = first_match + (week - 1) * 7;
+                while day >= first + days_in_month(year, month) {
+                    day -= 7;
+                }
+                day
+            }
+        }
+    }
+}
+
+impl PosixTz {
+    fn fixed(abbr: &str, offset: i64) -> Self {
+        Self {
+            std_abbr: abbr.to_string(),
+            std_offset: offset,
+            dst: None,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut rest = s;
+        let std_abbr = take_abbr(&mut rest)?;
+        let std_offset = -take_clock(&mut rest)?;
+
+        if rest.is_empty() {
+            return Some(Self::fixed(&std_abbr, std_offset));
+        }
+
+        let dst_abbr = take_abbr(&mut rest)?;
+        let dst_offset = if rest.starts_with(',') || rest.is_empty() {
+            std_offset + 3600
+        } else {
+            -take_clock(&mut rest)?
+        };
+
+        // Without explicit rules, POSIX defaults to the US rules
+        let (start, start_time, end, end_time) = if rest.is_empty() {
+            (DstDate::MonthWeekDay(3, 2, 0), 7200, DstDate::MonthWeekDay(11, 1, 0), 7200)
+        } else {
+            let rules = rest.strip_prefix(',')?;
+            let (start, end) = rules.split_once(',')?;
+            let (start, start_time) = parse_rule(start)?;
+            let (end, end_time) = parse_rule(end)?;
+            (start, start_time, end, end_time)
+        };
+
+        Some(Self {
+            std_abbr,
+            std_offset,
+            dst: Some(PosixDst {
+                abbr: dst_abbr,
+                offset: dst_offset,
+                start,
+                start_time,
+                end,
+                end_time,
+            }),
+        })
+    }
+
+    fn offset_at(&self, utc_secs: i64) -> LocalOffset {
+        let standard = LocalOffset {
+            offset: self.std_offset,
+            is_dst: false,
+            abbreviation: self.std_abbr.clone(),
+        };
+        let Some(dst) = &self.dst else { return standard };
+
+        let (year, _, _) = civil_from_days((utc_secs + self.std_offset).div_euclid(SECS_PER_DAY));
+        let start = dst.start.day_in(year) * SECS_PER_DAY + dst.start_time - self.std_offset;
+        let end = dst.end.day_in(year) * SECS_PER_DAY + dst.end_time - dst.offset;
+
+        // Southern hemisphere zones have DST across the new year
+        let in_dst = if start < end {
+            utc_secs >= start && utc_secs < end
+        } else {
+            !(utc_secs >= end && utc_secs < start)
+        };
+
+        if in_dst {
+            LocalOffset {
+                offset: dst.offset,
+                is_dst: true,
+                abbreviation: dst.abbr.clone(),
+            }
+        } else {
+            standard
+        }
+    }
+}
+
+/// Zone abbreviation: letters ("CET") or quoted ("<+0330>")
+fn take_abbr(rest: &mut &str) -> Option<String> {
+    let (abbr, remaining) = if let Some(quoted) = rest.strip_prefix('<') {
+        let end = quoted.find('>')?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        (&rest[..end], &rest[end..])
+    };
+    if abbr.len() < 3 {
+        return None;
+    }
+    *rest = remaining;
+    Some(abbr.to_string())
+}
+
+/// [+-]hh[:mm[:ss]] as seconds
+fn take_clock(rest: &mut &str) -> Option<i64> {
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, ':' | '+' | '-')))
+        .unwrap_or(rest.len());
+    let text = &rest[..end];
+    *rest = &rest[end..];
+    parse_clock(text)
+}
+
+fn parse_clock(text: &str) -> Option<i64> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(d) => (-1, d),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let mut parts = digits.split(':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next().map_or(Some(0), |m| m.parse().ok())?;
+    let seconds: i64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// "M3.2.0/2" -> (date, seconds after midnight); the time defaults to 02:00
+fn parse_rule(rule: &str) -> Option<(DstDate, i64)> {
+    let (date, time) = match rule.split_once('/') {
+        Some((date, time)) => (date, parse_clock(time)?),
+        None => (rule, 7200),
+    };
+
+    let date = if let Some(mwd) = date.strip_prefix('M') {
+        let mut parts = mwd.split('.').map(|p| p.parse::<i64>().ok());
+        DstDate::MonthWeekDay(parts.next()??, parts.next()??, parts.next()??)
+    } else if let Some(n) = date.strip_prefix('J') {
+        DstDate::Julian(n.parse().ok()?)
+    } else {
+        DstDate::ZeroBased(date.parse().ok()?)
+    };
+    Some((date, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix seconds of a UTC (or local) date and time
+    fn secs(year: i64, month: i64, day: i64, hour: i64, minute: i64) -> i64 {
+        days_from_civil(year, month, day) * SECS_PER_DAY + hour * 3600 + minute * 60
+    }
+
+    fn posix(tz: &str) -> TimeZone {
+        TimeZone::from_rule(tz, PosixTz::parse(tz).unwrap())
+    }
+
+    /// A TZif file: a v1 block, and for version '2'+ a v2 block and footer
+    fn tzif(version: u8, transitions: &[(i64, u8)], types: &[(i32, bool, u8)], abbrs: &[u8], footer: &str) -> Vec<u8> {
+        let block = |time_size: usize| {
+            let mut b = b"TZif".to_vec();
+            b.push(version);
+            b.extend([0; 15]);
+            for count in [0, 0, 0, transitions.len(), types.len(), abbrs.len()] {
+                b.extend((count as u32).to_be_bytes());
+            }
+            for &(at, _) in transitions {
+                match time_size {
+                    8 => b.extend(at.to_be_bytes()),
+                    _ => b.extend((at as i32).to_be_bytes()),
+                }
+            }
+            b.extend(transitions.iter().map(|&(_, kind)| kind));
+            for &(offset, dst, abbr) in types {
+                b.extend(offset.to_be_bytes());
+                b.extend([dst as u8, abbr]);
+            }
+            b.extend(abbrs);
+            b
+        };
+        let mut bytes = block(4);
+        if version >= b'2' {
+            bytes.extend(block(8));
+            bytes.extend(format!("\n{}\n", footer).bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn posix_rule_switches_at_the_stated_times() {
+        let berlin = posix("CET-1CEST,M3.5.0,M10.5.0/3");
+        // DST runs from 01:00 UTC on the last Sunday of March to 01:00 UTC
+        // on the last Sunday of October
+        assert_eq!(berlin.offset_at(secs(2024, 3, 31, 0, 59)).offset, 3600);
+        let summer = berlin.offset_at(secs(2024, 3, 31, 1, 0));
+        assert_eq!((summer.offset, summer.is_dst, summer.abbreviation.as_str()), (7200, true, "CEST"));
+        assert_eq!(berlin.offset_at(secs(2024, 10, 27, 0, 59)).offset, 7200);
+        assert_eq!(berlin.offset_at(secs(2024, 10, 27, 1, 0)).offset, 3600);
+    }
+
+    #[test]
+    fn southern_hemisphere_dst_spans_the_new_year() {
+        let sydney = posix("AEST-10AEDT,M10.1.0,M4.1.0/3");
+        assert_eq!(sydney.offset_at(secs(2024, 1, 15, 0, 0)).offset, 11 * 3600);
+        assert_eq!(sydney.offset_at(secs(2024, 7, 15, 0, 0)).offset, 10 * 3600);
+    }
+
+    #[test]
+    fn posix_rule_without_dates_uses_us_rules() {
+        let new_york = posix("EST5EDT");
+        assert_eq!(new_york.offset_at(secs(2024, 7, 1, 0, 0)).offset, -4 * 3600);
+        assert_eq!(new_york.offset_at(secs(2024, 1, 1, 0, 0)).offset, -5 * 3600);
+        // Second Sunday of March, 02:00 EST
+        assert_eq!(new_york.offset_at(secs(2024, 3, 10, 7, 0)).offset, -4 * 3600);
+    }
+
+    #[test]
+    fn julian_rule_days() {
+        assert_eq!(DstDate::Julian(60).day_in(2024), days_from_civil(2024, 3, 1));
+        assert_eq!(DstDate::ZeroBased(59).day_in(2024), days_from_civil(2024, 2, 29));
+    }
+
+    #[test]
+    fn local_time_in_a_gap_moves_past_it() {
+        let berlin = posix("CET-1CEST,M3.5.0,M10.5.0/3");
+        // 02:30 doesn't exist on 2024-03-31; it becomes 03:30 CEST
+        assert_eq!(berlin.to_utc(secs(2024, 3, 31, 2, 30)), secs(2024, 3, 31, 1, 30));
+    }
+
+    #[test]
+    fn repeated_local_time_is_the_first() {
+        let berlin = posix("CET-1CEST,M3.5.0,M10.5.0/3");
+        // 02:30 happens twice on 2024-10-27; the first is still CEST
+        assert_eq!(berlin.to_utc(secs(2024, 10, 27, 2, 30)), secs(2024, 10, 27, 0, 30));
+        assert_eq!(berlin.to_utc(secs(2024, 7, 1, 12, 0)), secs(2024, 7, 1, 10, 0));
+    }
+
+    #[test]
+    fn tzif_v2_transitions_then_footer() {
+        let bytes = tzif(b'2', &[(1000, 1), (2000, 0)], &[(3600, false, 0), (7200, true, 4)], b"AAA\0BBB\0", "CCC-3");
+        let zone = parse_tzif("Test/Zone", &bytes).unwrap();
+        assert_eq!(zone.offset_at(0).offset, 3600);
+        let dst = zone.offset_at(1500);
+        assert_eq!((dst.offset, dst.is_dst, dst.abbreviation.as_str()), (7200, true, "BBB"));
+        // After the last transition the footer rule applies
+        assert_eq!(zone.offset_at(3000).abbreviation, "CCC");
+        assert_eq!(zone.offset_at(3000).offset, 3 * 3600);
+    }
+
+    #[test]
+    fn tzif_v1_keeps_the_last_type() {
+        let bytes = tzif(0, &[(1000, 1)], &[(3600, false, 0), (7200, true, 4)], b"AAA\0BBB\0", "");
+        let zone = parse_tzif("Test/Old", &bytes).unwrap();
+        assert_eq!(zone.offset_at(999).offset, 3600);
+        assert_eq!(zone.offset_at(5000).offset, 7200);
+    }
+
+    #[test]
+    fn broken_tzif_is_rejected() {
+        let bytes = tzif(b'2', &[(1000, 1)], &[(3600, false, 0), (7200, true, 4)], b"AAA\0BBB\0", "CCC-3");
+        assert!(parse_tzif("Cut", &bytes[..bytes.len() / 2]).is_none());
+        // A transition pointing at a type that doesn't exist
+        let bad = tzif(b'2', &[(1000, 5)], &[(3600, false, 0)], b"AAA\0", "AAA-1");
+        assert!(parse_tzif("Bad", &bad).is_none());
+        assert!(parse_tzif("Empty", b"").is_none());
+    }
+}
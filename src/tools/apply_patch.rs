@@ -0,0 +1,684 @@
+//! Apply Patch Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! For refactors spanning many files, one unified diff is far less chatty
+//! than dozens of edit_file calls. The patch may create, delete and rename
+//! files (git-style headers), and it is applied all-or-nothing:
+//!
+//! 1. Parse the diff into per-file patches
+//! 2. Compute every file's new contents in memory; any hunk that doesn't
+//!    fit aborts the whole patch before anything is written
+//! 3. Write the results, restoring every original file if a write fails
+//!
+//! Hunks are matched fuzzily: first at the line number in the header, then
+//! within a few hundred lines of it, then ignoring whitespace differences.
+//! Away from the header's line a match must be the only one in that window,
+//! so a hunk with little context can't land in the wrong function.
+//!
+//! Like edit_file, every file the patch changes must have been read first
+//! and be unchanged on disk since.
+
+use super::file_tracker::FileTracker;
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How far from the header's line a hunk may be found
+const SEARCH_WINDOW: usize = 300;
+
+/// One line of a hunk body
+#[derive(Debug)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug)]
+struct Hunk {
+    /// 1-based line in the original file where the hunk starts
+    old_start: usize,
+    lines: Vec<HunkLine>,
+    /// "\ No newline at end of file" after the hunk's last old line
+    old_no_newline: bool,
+    /// ... after its last new line
+    new_no_newline: bool,
+}
+
+impl Hunk {
+    /// The lines the hunk expects to find
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// The lines the hunk leaves behind, given the file lines it matched
+    ///
+    /// Context lines keep the file's version, so a whitespace-fuzzy match
+    /// doesn't rewrite lines the hunk wasn't meant to change.
+    fn new_lines(&self, matched: &[String]) -> Vec<String> {
+        let mut matched = matched.iter();
+        let mut result = Vec::new();
+        for line in &self.lines {
+            match line {
+                HunkLine::Context(s) => result.push(matched.next().cloned().unwrap_or_else(|| s.clone())),
+                HunkLine::Remove(_) => {
+                    matched.next();
+                }
+                HunkLine::Add(s) => result.push(s.clone()),
+            }
+        }
+        result
+    }
+}
+
+/// Everything the diff says about one file
+#[derive(Debug, Default)]
+struct FilePatch {
+    /// None for a newly created file
+    old_path: Option<String>,
+    /// None for a deleted file
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+/// What applying one FilePatch will do to the filesystem
+struct PlannedChange {
+    /// Path and final contents to write (None when the file is only removed)
+    target: Option<(PathBuf, String)>,
+    /// File that disappears: the deleted file, or a rename's old name
+    remove: Option<PathBuf>,
+    report: String,
+}
+
+/// How strictly a hunk's lines must match the file
+#[derive(Clone, Copy, PartialEq)]
+enum Fuzz {
+    Exact,
+    TrailingWhitespace,
+    AllWhitespace,
+}
+
+pub struct ApplyPatchTool {
+    tracker: FileTracker,
+    workspace: Workspace,
+}
+
+impl ApplyPatchTool {
+    pub fn new(tracker: FileTracker, workspace: Workspace) -> Self {
+        Self { tracker, workspace }
+    }
+}
+
+impl Default for ApplyPatchTool {
+    fn default() -> Self {
+        Self::new(FileTracker::new(), Workspace::default())
+    }
+}
+
+impl ToolExecutor for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "apply_patch"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "apply_patch",
+            "Apply a unified diff that may touch several files. Supports git-style \
+             creation (--- /dev/null), deletion (+++ /dev/null) and renames \
+             (rename from/rename to). Hunk header line counts must match the hunk body. Applied atomically: if any hunk fails, no file changes. \
+             Files it changes must have been read with read_file first. Returns a per-file report.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "patch": {
+                        "type": "string",
+                        "description": "The unified diff to apply"
+                    }
+                },
+                "required": ["patch"]
+            }),
+        )
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        let Some(Ok(files)) = input["patch"].as_str().map(parse_patch) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<String> = Vec::new();
+        for path in files.into_iter().flat_map(|f| [f.old_path, f.new_path]).flatten() {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let patch = input["patch"]
+            .as_str()
+            .ok_or("Missing required parameter: patch")?;
+
+        let files = parse_patch(patch)?;
+        if files.is_empty() {
+            return Err("No file changes found in the patch".to_string());
+        }
+        for path in files.iter().flat_map(|f| [&f.old_path, &f.new_path]).flatten() {
+            self.workspace.check(Path::new(path))?;
+        }
+        for path in files.iter().filter_map(|f| f.old_path.as_ref()) {
+            self.tracker.check_fresh(Path::new(path))?;
+        }
+
+        // Plan everything before touching the disk
+        let mut plan = Vec::new();
+        let mut errors = Vec::new();
+        for file in &files {
+            match plan_change(file) {
+                Ok(change) => plan.push(change),
+                Err(e) => errors.push(e),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(format!(
+                "Patch not applied; no files were changed:\n{}",
+                errors.join("\n")
+            ));
+        }
+
+        commit(&plan)?;
+
+        // The model wrote these contents, so it knows them
+        for (path, _) in plan.iter().filter_map(|c| c.target.as_ref()) {
+            self.tracker.record(path);
+        }
+
+        Ok(ToolOutput::text(
+            plan.iter()
+                .map(|c| c.report.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ))
+    }
+
+    fn echo_output(&self) -> bool {
+        true
+    }
+}
+
+/// Split a (possibly multi-file) unified diff into per-file patches
+fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut lines = patch.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if line.starts_with("diff --git ") {
+            files.extend(current.take());
+            current = Some(FilePatch::default());
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            current.get_or_insert_with(FilePatch::default).old_path = Some(path.to_string());
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            current.get_or_insert_with(FilePatch::default).new_path = Some(path.to_string());
+        } else if let Some(header) = line.strip_prefix("--- ") {
+            // A "---" outside a git header starts a new plain diff
+            if current.as_ref().is_some_and(|f| !f.hunks.is_empty()) {
+                files.extend(current.take());
+            }
+            current.get_or_insert_with(FilePatch::default).old_path = header_path(header, "a/");
+        } else if let Some(header) = line.strip_prefix("+++ ") {
+            current.get_or_insert_with(FilePatch::default).new_path = header_path(header, "b/");
+        } else if line.starts_with("@@") {
+            let file = current
+                .as_mut()
+                .ok_or_else(|| format!("Hunk without a file header: {}", line))?;
+            let (old_start, mut old_left, mut new_left) = parse_hunk_header(line)?;
+
+            // The header's line counts say where the body ends; its content
+            // can't, since removing "-- note" gives the body line "--- note"
+            let mut body = Vec::new();
+            let (mut old_no_newline, mut new_no_newline) = (false, false);
+            while old_left > 0 || new_left > 0 {
+                let next = lines.next().ok_or_else(|| {
+                    format!(
+                        "Hunk {} ends early: {} old and {} new lines missing; fix the counts",
+                        line, old_left, new_left
+                    )
+                })?;
+                let hunk_line = if let Some(rest) = next.strip_prefix(' ') {
+                    HunkLine::Context(rest.to_string())
+                } else if next.is_empty() {
+                    // Some editors strip the space from empty context lines
+                    HunkLine::Context(String::new())
+                } else if let Some(rest) = next.strip_prefix('-') {
+                    HunkLine::Remove(rest.to_string())
+                } else if let Some(rest) = next.strip_prefix('+') {
+                    HunkLine::Add(rest.to_string())
+                } else {
+                    return Err(format!("Hunk {} has an unexpected line: {}", line, next));
+                };
+                let (old, new) = match hunk_line {
+                    HunkLine::Context(_) => (1, 1),
+                    HunkLine::Remove(_) => (1, 0),
+                    HunkLine::Add(_) => (0, 1),
+                };
+                if old > old_left || new > new_left {
+                    return Err(format!(
+                        "Hunk {} has more lines than its header counts (at: {}); fix the counts",
+                        line, next
+                    ));
+                }
+                old_left -= old;
+                new_left -= new;
+                body.push(hunk_line);
+
+                // "\ No newline at end of file" applies to the line before it
+                if lines.next_if(|l| l.starts_with('\\')).is_some() {
+                    old_no_newline |= old == 1;
+                    new_no_newline |= new == 1;
+                }
+            }
+            file.hunks.push(Hunk { old_start, lines: body, old_no_newline, new_no_newline });
+        }
+        // Anything else (index lines, file modes, commentary) is ignored
+    }
+    files.extend(current);
+
+    // Headers without hunks or a path change (e.g. mode-only) do nothing
+    Ok(files
+        .into_iter()
+        .filter(|f| f.old_path.is_some() || f.new_path.is_some())
+        .filter(|f| !f.hunks.is_empty() || f.old_path != f.new_path)
+        .collect())
+}
+
+/// Path from a ---/+++ header, without the a/ b/ prefix; None for /dev/null
+fn header_path(header: &str, prefix: &str) -> Option<String> {
+    // Drop a trailing timestamp ("file.rs\t2024-01-01 ...")
+    let path = header.split('\t').next().unwrap_or(header).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// "@@ -12,7 +12,8 @@ fn main" -> (12, 7, 8); a missing count means 1
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), String> {
+    let malformed = || format!("Malformed hunk header: {}", line);
+    let range = |prefix: char| -> Result<(usize, usize), String> {
+        let range = line
+            .split_whitespace()
+            .find_map(|part| part.strip_prefix(prefix))
+            .ok_or_else(malformed)?;
+        let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+        Ok((start.parse().map_err(|_| malformed())?, count.parse().map_err(|_| malformed())?))
+    };
+    let (old_start, old_count) = range('-')?;
+    let (_, new_count) = range('+')?;
+    Ok((old_start, old_count, new_count))
+}
+
+/// Work out the final contents of one file without writing anything
+fn plan_change(file: &FilePatch) -> Result<PlannedChange, String> {
+    match (&file.old_path, &file.new_path) {
+        (None, None) => Err("A file patch has neither an old nor a new path".to_string()),
+
+        // Creation: every hunk line must be an addition
+        (None, Some(new)) => {
+            let path = PathBuf::from(new);
+            if path.exists() {
+                return Err(format!("{}: cannot create, file already exists", new));
+            }
+            let content = apply_hunks(new, "", &file.hunks)?.0;
+            Ok(PlannedChange {
+                report: format!("{}: created ({} lines)", new, content.lines().count()),
+                target: Some((path, content)),
+                remove: None,
+            })
+        }
+
+        // Deletion: check the hunks still describe the file, then remove it
+        (Some(old), None) => {
+            let original = read(old)?;
+            apply_hunks(old, &original, &file.hunks)?;
+            Ok(PlannedChange {
+                target: None,
+                remove: Some(PathBuf::from(old)),
+                report: format!("{}: deleted", old),
+            })
+        }
+
+        // Modification, possibly with a rename
+        (Some(old), Some(new)) => {
+            let original = read(old)?;
+            let (content, notes) = apply_hunks(old, &original, &file.hunks)?;
+            let renamed = old != new;
+            if renamed && Path::new(new).exists() {
+                return Err(format!("{}: cannot rename to {}, target exists", old, new));
+            }
+
+            let mut report = if renamed {
+                format!("{}: renamed to {}", old, new)
+            } else {
+                format!("{}: modified", old)
+            };
+            if !file.hunks.is_empty() {
+                report.push_str(&format!(" ({} hunks", file.hunks.len()));
+                if !notes.is_empty() {
+                    report.push_str(&format!("; {}", notes.join("; ")));
+                }
+                report.push(')');
+            }
+
+            Ok(PlannedChange {
+                target: Some((PathBuf::from(new), content)),
+                remove: renamed.then(|| PathBuf::from(old)),
+                report,
+            })
+        }
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: cannot read ({})", path, e))
+}
+
+/// Apply hunks in order; returns the new text and notes about fuzzy matches
+///
+/// The result keeps the file's line ending (CRLF if its first line has
+/// one). Whether it ends in a newline only changes when a hunk at the end
+/// of the file carries a "\ No newline at end of file" marker.
+fn apply_hunks(path: &str, original: &str, hunks: &[Hunk]) -> Result<(String, Vec<String>), String> {
+    let mut lines: Vec<String> = original.lines().map(str::to_string).collect();
+    let eol = match original.find('\n') {
+        Some(i) if original[..i].ends_with('\r') => "\r\n",
+        _ => "\n",
+    };
+    let mut trailing_newline = original.is_empty() || original.ends_with('\n');
+    let mut notes = Vec::new();
+
+    // Line numbers shift as earlier hunks add or remove lines
+    let mut shift: isize = 0;
+
+    for (n, hunk) in hunks.iter().enumerate() {
+        let old = hunk.old_lines();
+        // A pure insertion ("@@ -5,0 +6,2 @@") goes after line old_start,
+        // anything else starts at it
+        let first = if old.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (first as isize + shift).max(0) as usize;
+
+        let (at, fuzz) = find_hunk(&lines, &old, expected)
+            .map_err(|e| format!("{}: hunk {} (expected near line {}) {}", path, n + 1, hunk.old_start, e))?;
+
+        if at != expected || fuzz != Fuzz::Exact {
+            let offset = at as isize - expected as isize;
+            let mut note = format!("hunk {} at line {}", n + 1, at + 1);
+            if offset != 0 {
+                note.push_str(&format!(" (offset {:+})", offset));
+            }
+            if fuzz != Fuzz::Exact {
+                note.push_str(" ignoring whitespace");
+            }
+            notes.push(note);
+        }
+
+        let at_end = at + old.len() == lines.len();
+        if at_end && (hunk.old_no_newline || hunk.new_no_newline) {
+            trailing_newline = !hunk.new_no_newline;
+        }
+
+        let new = hunk.new_lines(&lines[at..at + old.len()]);
+        shift += new.len() as isize - old.len() as isize;
+        lines.splice(at..at + old.len(), new);
+    }
+
+    let mut text = lines.join(eol);
+    if trailing_newline && !text.is_empty() {
+        text.push_str(eol);
+    }
+    Ok((text, notes))
+}
+
+/// Find where `old` occurs, preferring the expected line and exact matches
+///
+/// Away from the expected line, a match must be the only one within
+/// SEARCH_WINDOW lines at that fuzz level.
+fn find_hunk(lines: &[String], old: &[&str], expected: usize) -> Result<(usize, Fuzz), String> {
+    const NO_MATCH: &str = "does not match the file";
+    if old.is_empty() {
+        return Ok((expected.min(lines.len()), Fuzz::Exact));
+    }
+    if old.len() > lines.len() {
+        return Err(NO_MATCH.to_string());
+    }
+
+    let last_start = lines.len() - old.len();
+    let window = expected.saturating_sub(SEARCH_WINDOW)..=(expected + SEARCH_WINDOW).min(last_start);
+    for fuzz in [Fuzz::Exact, Fuzz::TrailingWhitespace, Fuzz::AllWhitespace] {
+        if expected <= last_start && matches_at(lines, old, expected, fuzz) {
+            return Ok((expected, fuzz));
+        }
+        let found: Vec<usize> = window.clone().filter(|&start| matches_at(lines, old, start, fuzz)).collect();
+        match found[..] {
+            [] => continue,
+            [start] => return Ok((start, fuzz)),
+            _ => {
+                let at: Vec<String> = found.iter().map(|start| (start + 1).to_string()).collect();
+                return Err(format!(
+                    "matches at several lines ({}); add context lines or fix the line number",
+                    at.join(", ")
+                ));
+            }
+        }
+    }
+    Err(NO_MATCH.to_string())
+}
+
+fn matches_at(lines: &[String], old: &[&str], start: usize, fuzz: Fuzz) -> bool {
+    old.iter().enumerate().all(|(i, expected)| {
+        let actual = lines[start + i].as_str();
+        match fuzz {
+            Fuzz::Exact => actual == *expected,
+            Fuzz::TrailingWhitespace => actual.trim_end() == expected.trim_end(),
+            Fuzz::AllWhitespace => actual.trim() == expected.trim(),
+        }
+    })
+}
+
+/// Write the planned changes, restoring everything if any step fails
+fn commit(plan: &[PlannedChange]) -> Result<(), String> {
+    // Originals of every path we are about to touch (None = didn't exist)
+    let mut backups: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+    for change in plan {
+        let paths = change.target.iter().map(|(p, _)| p).chain(change.remove.iter());
+        for path in paths {
+            backups.push((path.clone(), fs::read(path).ok()));
+        }
+    }
+
+    let result = plan.iter().try_for_each(|change| {
+        if let Some((path, content)) = &change.target {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("{}: cannot create directory ({})", parent.display(), e))?;
+            }
+            fs::write(path, content).map_err(|e| format!("{}: write failed ({})", path.display(), e))?;
+        }
+        if let Some(path) = &change.remove {
+            fs::remove_file(path).map_err(|e| format!("{}: delete failed ({})", path.display(), e))?;
+        }
+        Ok::<(), String>(())
+    });
+
+    if let Err(e) = result {
+        for (path, original) in backups.iter().rev() {
+            let _ = match original {
+                Some(bytes) => fs::write(path, bytes),
+                None => fs::remove_file(path),
+            };
+        }
+        return Err(format!("Patch rolled back, no files were changed: {}", e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A scratch workspace and a tool that has "read" every file in `files`
+    fn workspace(files: &[(&str, &str)]) -> (TempDir, ApplyPatchTool) {
+        let dir = TempDir::new().unwrap();
+        let tracker = FileTracker::new();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            tracker.record(&path);
+        }
+        let tool = ApplyPatchTool::new(tracker, Workspace::new(dir.path()).unwrap());
+        (dir, tool)
+    }
+
+    fn run(tool: &ApplyPatchTool, patch: &str) -> Result<ToolOutput, String> {
+        tool.execute(json!({ "patch": patch }))
+    }
+
+    /// Apply a single-file patch to `original` in memory
+    fn apply(original: &str, patch: &str) -> Result<String, String> {
+        let files = parse_patch(patch)?;
+        apply_hunks("f", original, &files[0].hunks).map(|(text, _)| text)
+    }
+
+    #[test]
+    fn insertion_only_hunk_goes_after_its_line() {
+        let patch = "--- a/f\n+++ b/f\n@@ -2,0 +3,2 @@\n+x\n+y\n";
+        assert_eq!(apply("a\nb\nc\n", patch).unwrap(), "a\nb\nx\ny\nc\n");
+    }
+
+    #[test]
+    fn insertion_at_the_top() {
+        let patch = "--- a/f\n+++ b/f\n@@ -0,0 +1 @@\n+first\n";
+        assert_eq!(apply("a\n", patch).unwrap(), "first\na\n");
+    }
+
+    #[test]
+    fn insertion_after_an_earlier_hunk_shifts() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1 +1,2 @@\n a\n+a2\n@@ -3,0 +4 @@\n+d\n";
+        assert_eq!(apply("a\nb\nc\n", patch).unwrap(), "a\na2\nb\nc\nd\n");
+    }
+
+    #[test]
+    fn crlf_file_keeps_its_line_endings() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n";
+        assert_eq!(apply("a\r\nb\r\nc\r\n", patch).unwrap(), "a\r\nB\r\nc\r\n");
+    }
+
+    #[test]
+    fn removes_the_final_newline() {
+        let patch = "--- a/f\n+++ b/f\n@@ -2 +2 @@\n-b\n+b\n\\ No newline at end of file\n";
+        assert_eq!(apply("a\nb\n", patch).unwrap(), "a\nb");
+    }
+
+    #[test]
+    fn adds_the_final_newline() {
+        let patch = "--- a/f\n+++ b/f\n@@ -2 +2 @@\n-b\n\\ No newline at end of file\n+b\n";
+        assert_eq!(apply("a\nb", patch).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn unmarked_hunk_keeps_a_missing_final_newline() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n-a\n+A\n b\n";
+        assert_eq!(apply("a\nb", patch).unwrap(), "A\nb");
+    }
+
+    #[test]
+    fn moved_hunk_is_found_nearby() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n x\n-y\n+Y\n";
+        assert_eq!(apply("a\nb\nx\ny\n", patch).unwrap(), "a\nb\nx\nY\n");
+    }
+
+    #[test]
+    fn ambiguous_moved_hunk_is_refused() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-}\n+};\n";
+        let err = apply("a\n}\nb\n}\n", patch).unwrap_err();
+        assert!(err.contains("several lines (2, 4)"), "{}", err);
+    }
+
+    #[test]
+    fn hunk_far_from_its_line_is_refused() {
+        let mut original = "x\n".repeat(SEARCH_WINDOW + 10);
+        original.push_str("target\n");
+        let patch = "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-target\n+done\n";
+        assert!(apply(&original, patch).unwrap_err().contains("does not match"));
+    }
+
+    #[test]
+    fn renames_and_edits_a_file() {
+        let (dir, tool) = workspace(&[("old.txt", "a\nb\n")]);
+        let (old, new) = (dir.path().join("old.txt"), dir.path().join("new.txt"));
+        let patch = format!(
+            "diff --git a/x b/x\nrename from {}\nrename to {}\n--- {0}\n+++ {1}\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n",
+            old.display(),
+            new.display()
+        );
+        run(&tool, &patch).unwrap();
+        assert!(!old.exists());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "a\nB\n");
+        // The patched file can be edited again without another read
+        tool.tracker.check_fresh(&new).unwrap();
+    }
+
+    #[test]
+    fn deletes_a_file() {
+        let (dir, tool) = workspace(&[("gone.txt", "x\n")]);
+        let path = dir.path().join("gone.txt");
+        let patch = format!("--- {}\n+++ /dev/null\n@@ -1 +0,0 @@\n-x\n", path.display());
+        run(&tool, &patch).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn failed_write_rolls_back_earlier_files() {
+        let (dir, tool) = workspace(&[("a.txt", "a\n"), ("blocker", "")]);
+        let a = dir.path().join("a.txt");
+        // blocker is a file, so its "subdirectory" can't be created
+        let patch = format!(
+            "--- {}\n+++ {0}\n@@ -1 +1 @@\n-a\n+A\n--- /dev/null\n+++ {}\n@@ -0,0 +1 @@\n+new\n",
+            a.display(),
+            dir.path().join("blocker/new.txt").display()
+        );
+        let err = run(&tool, &patch).unwrap_err();
+        assert!(err.contains("rolled back"), "{}", err);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn refuses_a_file_changed_since_it_was_read() {
+        let (dir, tool) = workspace(&[("a.txt", "a\n")]);
+        let a = dir.path().join("a.txt");
+        // Make sure the modification time moves
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&a, "b\n").unwrap();
+        let patch = format!("--- {}\n+++ {0}\n@@ -1 +1 @@\n-b\n+c\n", a.display());
+        assert!(run(&tool, &patch).unwrap_err().contains("modified since"));
+        assert_eq!(fs::read_to_string(&a).unwrap(), "b\n");
+    }
+
+    #[test]
+    fn refuses_a_file_that_was_never_read() {
+        let (dir, tool) = workspace(&[]);
+        let a = dir.path().join("a.txt");
+        fs::write(&a, "a\n").unwrap();
+        let patch = format!("--- {}\n+++ {0}\n@@ -1 +1 @@\n-a\n+b\n", a.display());
+        assert!(run(&tool, &patch).unwrap_err().contains("has not been read"));
+    }
+}
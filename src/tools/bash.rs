@@ -0,0 +1,461 @@
+//! Bash Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Runs shell commands so the agent can build, test and inspect a project.
+//! Each call is a fresh `bash` process, but the session feels continuous:
+//! after every command the working directory and exported variables are
+//! saved to a snapshot script that the next command sources first.
+//!
+//! Safety valves:
+//! - every command runs in its own process group, and the whole group is
+//!   killed on timeout (so `make` doesn't leave compilers running)
+//! - stdout and stderr are merged in order, and only the head and tail of
+//!   long output are kept
+//! - optionally, commands run inside a `Sandbox` (see `sandbox`)
+
+use super::{CancelToken, Sandbox, ToolExecutor, ToolOutput};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Timeout when the model doesn't ask for one
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// Upper bound on what the model may ask for
+const MAX_TIMEOUT_SECS: u64 = 600;
+
+/// Extra time the registry allows beyond the command's own timeout, so the
+/// command's timeout (which reports the output so far) fires first
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// Bytes kept in memory from the start and from the end of long output
+/// (what reaches the model is cut down further by the registry's output
+/// budget, with the full text saved to a file)
+const HEAD_BYTES: usize = 2 * 1024 * 1024;
+const TAIL_BYTES: usize = 2 * 1024 * 1024;
+
+pub struct BashTool {
+    /// Script restoring cwd and exports, rewritten after every command
+    snapshot: PathBuf,
+    /// Private directory holding the snapshot, removed on drop. Every
+    /// command sources the snapshot, so nobody else may be able to write it.
+    _dir: TempDir,
+    sandbox: Option<Sandbox>,
+}
+
+impl BashTool {
+    pub fn new() -> Result<Self, String> {
+        let dir = tempfile::Builder::new()
+            .prefix("johnathan-bash-")
+            .permissions(fs::Permissions::from_mode(0o700))
+            .tempdir()
+            .map_err(|e| format!("Cannot create a directory for the shell snapshot: {}", e))?;
+        let snapshot = dir.path().join("snapshot.sh");
+
+        // Start in the directory the agent was launched from
+        if let Ok(cwd) = std::env::current_dir() {
+            let _ = fs::write(&snapshot, format!("cd {}\n", shell_quote(&cwd.to_string_lossy())));
+        }
+
+        Ok(Self {
+            snapshot,
+            _dir: dir,
+            sandbox: None,
+        })
+    }
+
+    /// Run every command inside `sandbox`
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Wrap the command so it starts from, and then updates, the snapshot
+    fn script(&self, command: &str) -> String {
+        let snapshot = shell_quote(&self.snapshot.to_string_lossy());
+        format!(
+            r#"__snapshot={snapshot}
+[ -f "$__snapshot" ] && . "$__snapshot" 2>/dev/null
+trap '__status=$?; {{ export -p | grep -Ev "^declare -x (BASHOPTS|SHELLOPTS|BASH_VERSINFO|EUID|PPID|UID)="; printf "cd %q\n" "$PWD"; }} > "$__snapshot.tmp" && mv "$__snapshot.tmp" "$__snapshot"; exit $__status' EXIT
+exec 2>&1
+{command}
+"#
+        )
+    }
+}
+
+impl ToolExecutor for BashTool {
+    fn name(&self) -> &str {
+        "bash"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "bash",
+            "Run a bash command and return its combined stdout/stderr and exit code. \
+             The working directory and exported environment variables persist between calls. \
+             Long output is truncated to its beginning and end.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The command to run"
+                    },
+                    "timeout": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 600,
+                        "description": "Timeout in seconds (default 120, max 600)"
+                    }
+                },
+                "required": ["command"]
+            }),
+        )
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        input["command"].as_str().map(command_parts).unwrap_or_default()
+    }
+
+    fn timeout(&self, input: &Value) -> Duration {
+        command_timeout(input) + TIMEOUT_MARGIN
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        self.execute_cancellable(input, &CancelToken::new())
+    }
+
+    fn execute_cancellable(&self, input: Value, cancel: &CancelToken) -> Result<ToolOutput, String> {
+        let command = input["command"]
+            .as_str()
+            .ok_or("Missing required parameter: command")?;
+        let timeout = command_timeout(&input);
+
+        let mut bash = Command::new("bash");
+        bash.arg("-c")
+            .arg(self.script(command))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // New process group (pgid = child pid) so we can kill everything it spawns
+            .process_group(0);
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(&mut bash);
+        }
+        let mut child = bash.spawn().map_err(|e| match &self.sandbox {
+            Some(sandbox) => format!(
+                "Failed to start bash in the sandbox ({}): {}",
+                sandbox.describe(),
+                e
+            ),
+            None => format!("Failed to start bash: {}", e),
+        })?;
+
+        // Drain output on a thread so a chatty command can't block on a full pipe
+        let capture = Arc::new(Mutex::new(Capture::default()));
+        let mut stdout = child.stdout.take().ok_or("Failed to capture output")?;
+        let reader = {
+            let capture = Arc::clone(&capture);
+            thread::spawn(move || {
+                let mut buf = [0u8; 8192];
+                while let Ok(n) = stdout.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    capture.lock().unwrap_or_else(|e| e.into_inner()).push(&buf[..n]);
+                }
+            })
+        };
+
+        let start = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if start.elapsed() >= timeout || cancel.is_cancelled() => {
+                    kill_group(child.id());
+                    let _ = child.wait();
+                    break None;
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(format!("Failed to wait for command: {}", e)),
+            }
+        };
+
+        // Background jobs may keep the pipe open; don't wait on them forever
+        let drain_deadline = Instant::now() + Duration::from_secs(1);
+        while !reader.is_finished() && Instant::now() < drain_deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let capture = capture.lock().unwrap_or_else(|e| e.into_inner());
+        let truncated = capture.omitted() > 0;
+        let mut output = capture.render();
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+
+        match status {
+            Some(status) => {
+                let code = status
+                    .code()
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "killed by signal".to_string());
+                output.push_str(&format!("[exit code: {}]", code));
+                if let Some(note) = self.sandbox.as_ref().and_then(|s| s.explain(status.code(), &output)) {
+                    output.push_str(&format!("\n{}", note));
+                }
+            }
+            None if cancel.is_cancelled() => {
+                output.push_str("[cancelled; the command and its child processes were killed]")
+            }
+            None => output.push_str(&format!(
+                "[timed out after {}s; the command and its child processes were killed]",
+                timeout.as_secs()
+            )),
+        }
+        Ok(ToolOutput::text(output).truncated(truncated))
+    }
+}
+
+/// The timeout the model asked for, within bounds
+fn command_timeout(input: &Value) -> Duration {
+    Duration::from_secs(
+        input["timeout"]
+            .as_u64()
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+            .clamp(1, MAX_TIMEOUT_SECS),
+    )
+}
+
+/// Keeps the first HEAD_BYTES and last TAIL_BYTES of a stream
+#[derive(Default)]
+struct Capture {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    total: usize,
+}
+
+impl Capture {
+    fn push(&mut self, bytes: &[u8]) {
+        self.total += bytes.len();
+
+        let room = HEAD_BYTES.saturating_sub(self.head.len());
+        let (to_head, rest) = bytes.split_at(room.min(bytes.len()));
+        self.head.extend_from_slice(to_head);
+
+        self.tail.extend(rest);
+        let excess = self.tail.len().saturating_sub(TAIL_BYTES);
+        self.tail.drain(..excess);
+    }
+
+    /// Bytes dropped between head and tail
+    fn omitted(&self) -> usize {
+        self.total - self.head.len() - self.tail.len()
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::from_utf8_lossy(&self.head).into_owned();
+        let omitted = self.omitted();
+        if omitted > 0 {
+            text.push_str(&format!("\n\n[... {} bytes of output omitted ...]\n\n", omitted));
+        }
+        let tail: Vec<u8> = self.tail.iter().copied().collect();
+        text.push_str(&String::from_utf8_lossy(&tail));
+        text
+    }
+}
+
+/// SIGKILL every process in the group led by `pid`
+pub(super) fn kill_group(pid: u32) {
+    // A negative pid addresses the whole process group
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// Single-quote a string for bash
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Split a command line into the simple commands it runs, for permission
+/// rules: `cd x && make | tee log` gives `cd x`, `make`, `tee log`
+///
+/// Splits at `;`, `&&`, `||`, `|`, `&`, newlines, subshell parentheses,
+/// `$(...)` and backticks, but not inside quotes (except command
+/// substitution in double quotes, which still runs) or at `2>&1` / `&>`.
+///
+/// A redirect to a file becomes a part of its own, so writing the file
+/// needs permission too: `git status > ~/.bashrc` gives `git status` and
+/// `> ~/.bashrc`. Duplicating a descriptor (`2>&1`) and `>/dev/null` don't.
+pub(super) fn command_parts(command: &str) -> Vec<String> {
+    let chars: Vec<char> = command.chars().collect();
+    let mut parts = Vec::new();
+    let mut redirects = Vec::new();
+    let mut current = String::new();
+    // Open quotes and `$(`s, innermost last ('(' for `$(`)
+    let mut nesting: Vec<char> = Vec::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let split = match (nesting.last(), c) {
+            (Some('\''), '\'') | (Some('"'), '"') => {
+                nesting.pop();
+                false
+            }
+            (Some('\''), _) => false,
+            (_, '\\') => {
+                current.push(c);
+                current.extend(next);
+                i += 2;
+                continue;
+            }
+            (_, '`') => true,
+            (_, '$') if next == Some('(') => {
+                nesting.push('(');
+                i += 1;
+                true
+            }
+            (Some('"'), _) => false,
+            (Some('('), ')') => {
+                nesting.pop();
+                true
+            }
+            (_, '\'' | '"') => {
+                nesting.push(c);
+                false
+            }
+            // `>(cmd)` is process substitution, handled by the '(' split
+            (_, '>') if next != Some('(') => {
+                i = redirect(&chars, i, &mut current, &mut redirects);
+                continue;
+            }
+            (_, ';' | '\n' | '|' | '(' | ')') => true,
+            (_, '&') => {
+                let redirect = (i > 0 && chars[i - 1] == '>') || next == Some('>');
+                !redirect
+            }
+            _ => false,
+        };
+
+        if split {
+            parts.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+        i += 1;
+    }
+    parts.push(current);
+    parts.extend(redirects);
+
+    parts
+        .iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Read the output redirect whose `>` is at `chars[start]`: a redirect to a
+/// file goes to `redirects` as `> file` or `>> file`, a descriptor
+/// duplication stays in `current`. Returns the index after it.
+fn redirect(chars: &[char], start: usize, current: &mut String, redirects: &mut Vec<String>) -> usize {
+    let mut i = start + 1;
+    let append = chars.get(i) == Some(&'>');
+    if append {
+        i += 1;
+    }
+    // `>|` overrides noclobber; `>&word` duplicates a descriptor unless word is a file
+    if chars.get(i) == Some(&'|') {
+        i += 1;
+    }
+    let ampersand = chars.get(i) == Some(&'&');
+    if ampersand {
+        i += 1;
+    }
+    while chars.get(i).is_some_and(|c| *c == ' ' || *c == '\t') {
+        i += 1;
+    }
+
+    let mut target = String::new();
+    let mut quote = None;
+    while let Some(&c) = chars.get(i) {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => target.push(c),
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c.is_whitespace() || ";&|()<>`".contains(c) => break,
+            None => target.push(c),
+        }
+        i += 1;
+    }
+
+    let duplicate = ampersand && (target == "-" || (!target.is_empty() && target.chars().all(|c| c.is_ascii_digit())));
+    if duplicate {
+        current.extend(&chars[start..i]);
+        return i;
+    }
+
+    // `2>` and `&>` redirect the same file; drop the prefix from the command
+    let prefix = current.chars().rev().take_while(|c| c.is_ascii_digit() || *c == '&').count();
+    let before = current.chars().rev().nth(prefix);
+    if prefix > 0 && before.is_none_or(char::is_whitespace) {
+        current.truncate(current.len() - prefix);
+    }
+    if target != "/dev/null" {
+        redirects.push(format!("{} {}", if append { ">>" } else { ">" }, target));
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_at_control_operators() {
+        assert_eq!(command_parts("cd x && make | tee log"), ["cd x", "make", "tee log"]);
+        assert_eq!(command_parts("a; b || c & d\ne"), ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn quotes_do_not_split() {
+        assert_eq!(command_parts("echo 'a && b' \"c; d\""), ["echo 'a && b' \"c; d\""]);
+    }
+
+    #[test]
+    fn substitutions_and_subshells_are_parts() {
+        assert_eq!(command_parts("echo `whoami` (cd y; ls)"), ["echo", "whoami", "cd y", "ls"]);
+        assert!(command_parts("echo \"$(rm -rf x)\"").contains(&"rm -rf x".to_string()));
+    }
+
+    #[test]
+    fn file_redirects_are_parts() {
+        assert_eq!(command_parts("git status > ~/.bashrc"), ["git status", "> ~/.bashrc"]);
+        assert_eq!(command_parts("ls >> out.txt"), ["ls", ">> out.txt"]);
+        assert_eq!(command_parts("ls 2> err.log"), ["ls", "> err.log"]);
+        assert_eq!(command_parts("ls &> all.log"), ["ls", "> all.log"]);
+        assert_eq!(command_parts("echo a>b"), ["echo a", "> b"]);
+        assert_eq!(command_parts("ls >'my file'; rm x"), ["ls", "rm x", "> my file"]);
+    }
+
+    #[test]
+    fn descriptor_duplication_and_dev_null_are_not() {
+        assert_eq!(command_parts("make 2>&1 | less"), ["make 2>&1", "less"]);
+        assert_eq!(command_parts("ls 2>/dev/null"), ["ls"]);
+        assert_eq!(command_parts("ls >&-"), ["ls >&-"]);
+    }
+}
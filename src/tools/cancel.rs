@@ -0,0 +1,32 @@
+//! Cancellation - asking a running tool to stop
+//!
+//! Topic 9: Designing a Tool System
+//!
+//! Rust has no way to kill a thread, so a tool that runs too long can only
+//! be asked to stop. The registry hands each call a token and cancels it
+//! when the call's time is up; tools that run for a while (shell commands,
+//! cargo) check it while they wait and clean up after themselves. Tools
+//! that never check it are simply left to finish in the background.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared "please stop" flag for one tool call (cheap to clone)
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
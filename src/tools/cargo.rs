@@ -0,0 +1,472 @@
+//! Cargo Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Runs `cargo check/build/test/clippy` and reports what matters instead of
+//! the raw output. Compiler diagnostics come from `--message-format=json`
+//! (one JSON object per line on stdout); test results come from libtest's
+//! plain-text report, which cargo prints on the same stream.
+//!
+//! A failing build is condensed to one line per diagnostic (file, line,
+//! code, message) and a failing test run to the names of the failing tests
+//! and their panic messages.
+
+use super::bash::kill_group;
+use super::{CancelToken, Sandbox, ToolExecutor, ToolOutput};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+const MAX_TIMEOUT_SECS: u64 = 1800;
+
+/// Extra time the registry allows beyond cargo's own timeout
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// Diagnostics listed per level before the rest are only counted
+const MAX_DIAGNOSTICS: usize = 30;
+
+/// Lines of captured output kept per failing test
+const MAX_PANIC_LINES: usize = 12;
+
+pub struct CargoTool {
+    sandbox: Option<Sandbox>,
+}
+
+impl CargoTool {
+    pub fn new() -> Self {
+        Self { sandbox: None }
+    }
+
+    /// Run cargo, and with it build scripts and tests, inside `sandbox`
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+}
+
+impl Default for CargoTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolExecutor for CargoTool {
+    fn name(&self) -> &str {
+        "cargo"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "cargo",
+            "Run cargo check, build, test or clippy in the current directory and get a compact \
+             summary: each error and warning as file:line [code] message, then test totals, \
+             failing test names and their panic messages. Prefer this over running cargo through bash.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "enum": ["check", "build", "test", "clippy"],
+                        "description": "The cargo subcommand"
+                    },
+                    "package": {
+                        "type": "string",
+                        "description": "Only this workspace package (-p)"
+                    },
+                    "all_targets": {
+                        "type": "boolean",
+                        "description": "Include tests, examples and benches (--all-targets; not for test)"
+                    },
+                    "features": {
+                        "type": "string",
+                        "description": "Comma-separated features to enable"
+                    },
+                    "test_filter": {
+                        "type": "string",
+                        "description": "test: only run tests whose name contains this"
+                    },
+                    "timeout": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 1800,
+                        "description": "Timeout in seconds (default 600, max 1800)"
+                    }
+                },
+                "required": ["command"]
+            }),
+        )
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        input["command"].as_str().map(str::to_string).into_iter().collect()
+    }
+
+    fn timeout(&self, input: &Value) -> Duration {
+        cargo_timeout(input) + TIMEOUT_MARGIN
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        self.execute_cancellable(input, &CancelToken::new())
+    }
+
+    fn execute_cancellable(&self, input: Value, cancel: &CancelToken) -> Result<ToolOutput, String> {
+        let command = input["command"]
+            .as_str()
+            .ok_or("Missing required parameter: command")?;
+        if !matches!(command, "check" | "build" | "test" | "clippy") {
+            return Err(format!("Unsupported cargo command: {}", command));
+        }
+
+        let mut args = vec![command.to_string(), "--message-format=json".to_string()];
+        if let Some(package) = input["package"].as_str() {
+            args.extend(["-p".to_string(), checked_arg(package)?.to_string()]);
+        }
+        if let Some(features) = input["features"].as_str() {
+            args.extend(["--features".to_string(), checked_arg(features)?.to_string()]);
+        }
+        if input["all_targets"].as_bool().unwrap_or(false) && command != "test" {
+            args.push("--all-targets".to_string());
+        }
+        if command == "test"
+            && let Some(filter) = input["test_filter"].as_str()
+        {
+            args.push(checked_arg(filter)?.to_string());
+        }
+
+        let run = run_cargo(&args, cargo_timeout(&input), cancel, self.sandbox.as_ref())?;
+        let mut report = Report::default();
+        for line in run.stdout.lines() {
+            report.read_line(line);
+        }
+        report.finish();
+
+        Ok(ToolOutput::text(report.render(command, &run)).truncated(report.is_truncated()))
+    }
+}
+
+/// What a finished cargo process left behind
+struct Run {
+    stdout: String,
+    stderr: String,
+    success: bool,
+    timed_out: Option<Duration>,
+    cancelled: bool,
+}
+
+/// The timeout the model asked for, within bounds
+fn cargo_timeout(input: &Value) -> Duration {
+    Duration::from_secs(
+        input["timeout"]
+            .as_u64()
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+            .clamp(1, MAX_TIMEOUT_SECS),
+    )
+}
+
+fn run_cargo(args: &[String], timeout: Duration, cancel: &CancelToken, sandbox: Option<&Sandbox>) -> Result<Run, String> {
+    let mut cargo = Command::new("cargo");
+    cargo
+        .args(args)
+        .env("RUST_BACKTRACE", "0")
+        .env("CARGO_TERM_COLOR", "never")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    if let Some(sandbox) = sandbox {
+        sandbox.apply(&mut cargo);
+    }
+    let mut child = cargo.spawn().map_err(|e| format!("Failed to start cargo: {}", e))?;
+
+    // Readers append to shared buffers, so whatever arrived can be used
+    // even if a reader never finishes
+    let drain = |mut pipe: Box<dyn Read + Send>| {
+        let bytes = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&bytes);
+        let reader = thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            while let Ok(n) = pipe.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                sink.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(&buf[..n]);
+            }
+        });
+        (reader, bytes)
+    };
+    let (stdout_reader, stdout) = drain(Box::new(child.stdout.take().ok_or("Failed to capture output")?));
+    let (stderr_reader, stderr) = drain(Box::new(child.stderr.take().ok_or("Failed to capture output")?));
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if start.elapsed() >= timeout || cancel.is_cancelled() => {
+                kill_group(child.id());
+                let _ = child.wait();
+                break None;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for cargo: {}", e)),
+        }
+    };
+
+    // A build script or test may leave a background process holding the
+    // pipes; don't wait on it forever
+    let drain_deadline = Instant::now() + Duration::from_secs(1);
+    while !(stdout_reader.is_finished() && stderr_reader.is_finished()) && Instant::now() < drain_deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let text = |bytes: &Mutex<Vec<u8>>| String::from_utf8_lossy(&bytes.lock().unwrap_or_else(|e| e.into_inner())).into_owned();
+
+    Ok(Run {
+        stdout: text(&stdout),
+        stderr: text(&stderr),
+        success: status.is_some_and(|s| s.success()),
+        timed_out: (status.is_none() && !cancel.is_cancelled()).then_some(timeout),
+        cancelled: status.is_none() && cancel.is_cancelled(),
+    })
+}
+
+/// One compiler error or warning
+struct Diagnostic {
+    level: String,
+    location: String,
+    code: Option<String>,
+    message: String,
+    /// The primary span's label, e.g. "expected `u32`, found `String`"
+    label: Option<String>,
+}
+
+/// A failing test and what it printed
+struct FailedTest {
+    name: String,
+    output: Vec<String>,
+}
+
+#[derive(Default)]
+struct Report {
+    diagnostics: Vec<Diagnostic>,
+    /// The same diagnostic is reported once per target (lib, bin, tests)
+    seen: HashSet<(String, String)>,
+    passed: u64,
+    failed: u64,
+    ignored: u64,
+    failures: Vec<FailedTest>,
+    /// The test whose "---- name stdout ----" section we are inside
+    current_failure: Option<FailedTest>,
+}
+
+impl Report {
+    fn read_line(&mut self, line: &str) {
+        if line.starts_with('{')
+            && let Ok(message) = serde_json::from_str::<Value>(line)
+        {
+            if message["reason"] == "compiler-message" {
+                self.add_diagnostic(&message["message"]);
+            }
+            return;
+        }
+        self.read_test_line(line);
+    }
+
+    fn add_diagnostic(&mut self, message: &Value) {
+        let level = message["level"].as_str().unwrap_or("");
+        if !matches!(level, "error" | "warning") {
+            return;
+        }
+        let text = message["message"].as_str().unwrap_or("").to_string();
+
+        // Summary lines like "aborting due to 2 previous errors" have no span
+        let spans = message["spans"].as_array().map(Vec::as_slice).unwrap_or_default();
+        let primary = spans.iter().find(|s| s["is_primary"] == true).or(spans.first());
+        let Some(span) = primary else {
+            if !text.contains("previous error") && !text.contains("warning emitted") && !text.contains("warnings emitted") {
+                self.push_diagnostic(level, String::new(), None, text, None);
+            }
+            return;
+        };
+
+        let location = format!(
+            "{}:{}:{}",
+            span["file_name"].as_str().unwrap_or("?"),
+            span["line_start"],
+            span["column_start"]
+        );
+        let code = message["code"]["code"].as_str().map(str::to_string);
+        let label = span["label"].as_str().map(str::to_string);
+        self.push_diagnostic(level, location, code, text, label);
+    }
+
+    fn push_diagnostic(&mut self, level: &str, location: String, code: Option<String>, message: String, label: Option<String>) {
+        if self.seen.insert((location.clone(), message.clone())) {
+            self.diagnostics.push(Diagnostic {
+                level: level.to_string(),
+                location,
+                code,
+                message,
+                label,
+            });
+        }
+    }
+
+    fn read_test_line(&mut self, line: &str) {
+        // "test result: FAILED. 1 passed; 1 failed; 0 ignored; ..."
+        if let Some(totals) = line.strip_prefix("test result: ") {
+            self.close_failure();
+            for part in totals.split(['.', ';']) {
+                let mut words = part.split_whitespace();
+                if let (Some(count), Some(kind)) = (words.next(), words.next())
+                    && let Ok(count) = count.parse::<u64>()
+                {
+                    match kind {
+                        "passed" => self.passed += count,
+                        "failed" => self.failed += count,
+                        "ignored" => self.ignored += count,
+                        _ => {}
+                    }
+                }
+            }
+            return;
+        }
+
+        // "---- tests::bad stdout ----" starts a failing test's output
+        if let Some(rest) = line.strip_prefix("---- ")
+            && let Some(name) = rest.strip_suffix(" stdout ----")
+        {
+            self.close_failure();
+            self.current_failure = Some(FailedTest {
+                name: name.to_string(),
+                output: Vec::new(),
+            });
+            return;
+        }
+
+        // The "failures:" list that follows the captured output ends it
+        if line == "failures:" {
+            self.close_failure();
+            return;
+        }
+
+        if let Some(failure) = &mut self.current_failure
+            && !line.trim().is_empty()
+            && !line.starts_with("note: run with `RUST_BACKTRACE")
+        {
+            failure.output.push(line.to_string());
+        }
+    }
+
+    fn close_failure(&mut self) {
+        if let Some(failure) = self.current_failure.take() {
+            self.failures.push(failure);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.close_failure();
+    }
+
+    /// Whether render() leaves diagnostics or test output out
+    fn is_truncated(&self) -> bool {
+        let errors = self.diagnostics.iter().filter(|d| d.level == "error").count();
+        errors > MAX_DIAGNOSTICS
+            || self.diagnostics.len() - errors > MAX_DIAGNOSTICS
+            || self.failures.iter().any(|f| f.output.len() > MAX_PANIC_LINES)
+    }
+
+    fn render(&self, command: &str, run: &Run) -> String {
+        let errors: Vec<&Diagnostic> = self.diagnostics.iter().filter(|d| d.level == "error").collect();
+        let warnings: Vec<&Diagnostic> = self.diagnostics.iter().filter(|d| d.level == "warning").collect();
+
+        let verdict = match run.timed_out {
+            Some(timeout) => format!("TIMED OUT after {}s", timeout.as_secs()),
+            None if run.cancelled => "CANCELLED".to_string(),
+            None if run.success => "ok".to_string(),
+            None => "FAILED".to_string(),
+        };
+        let mut output = format!(
+            "cargo {}: {} ({} errors, {} warnings)\n",
+            command,
+            verdict,
+            errors.len(),
+            warnings.len()
+        );
+
+        for (title, list) in [("Errors", errors), ("Warnings", warnings)] {
+            if list.is_empty() {
+                continue;
+            }
+            output.push_str(&format!("\n{}:\n", title));
+            for diagnostic in list.iter().take(MAX_DIAGNOSTICS) {
+                output.push_str(&format!("  {}\n", diagnostic.render()));
+            }
+            if list.len() > MAX_DIAGNOSTICS {
+                output.push_str(&format!("  ... {} more\n", list.len() - MAX_DIAGNOSTICS));
+            }
+        }
+
+        if command == "test" && (self.passed + self.failed + self.ignored > 0 || !self.failures.is_empty()) {
+            output.push_str(&format!(
+                "\nTests: {} passed, {} failed, {} ignored\n",
+                self.passed, self.failed, self.ignored
+            ));
+            for failure in &self.failures {
+                output.push_str(&format!("\nFAILED {}\n", failure.name));
+                for line in failure.output.iter().take(MAX_PANIC_LINES) {
+                    output.push_str(&format!("    {}\n", line));
+                }
+                if failure.output.len() > MAX_PANIC_LINES {
+                    output.push_str(&format!("    ... {} more lines\n", failure.output.len() - MAX_PANIC_LINES));
+                }
+            }
+        }
+
+        // Nothing explains the failure (e.g. a bad manifest): show cargo's own errors
+        let explained = self.diagnostics.iter().any(|d| d.level == "error") || !self.failures.is_empty();
+        if !run.success && run.timed_out.is_none() && !run.cancelled && !explained {
+            let tail: Vec<&str> = run.stderr.lines().rev().take(20).collect();
+            output.push_str("\ncargo output:\n");
+            for line in tail.into_iter().rev() {
+                output.push_str(&format!("  {}\n", line));
+            }
+        }
+
+        output.trim_end().to_string()
+    }
+}
+
+impl Diagnostic {
+    /// "src/main.rs:2:26 [E0308] mismatched types: expected `u32`, found `&str`"
+    fn render(&self) -> String {
+        let mut line = self.location.clone();
+        if let Some(code) = &self.code {
+            line.push_str(&format!(" [{}]", code));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&self.message);
+        if let Some(label) = &self.label
+            && !label.is_empty()
+        {
+            line.push_str(&format!(": {}", label));
+        }
+        line
+    }
+}
+
+/// Values passed through to cargo must not be mistaken for options
+fn checked_arg(value: &str) -> Result<&str, String> {
+    if value.starts_with('-') {
+        Err(format!("Invalid argument: {}", value))
+    } else {
+        Ok(value)
+    }
+}
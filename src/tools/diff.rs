@@ -0,0 +1,24 @@
+//! Diff helper shared by the file-changing tools
+//!
+//! Both the model and the user see the same unified diff, so the model can
+//! check its edit landed as intended and the user can see what changed.
+
+use similar::TextDiff;
+
+/// Lines of unchanged context around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Unified diff between two versions of `path` ("" for a new file)
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let diff = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+
+    if diff.is_empty() {
+        "(no changes)".to_string()
+    } else {
+        diff
+    }
+}
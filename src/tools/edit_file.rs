@@ -0,0 +1,125 @@
+//! Edit File Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Changes code by exact string replacement: the model quotes the text it
+//! wants to change (`old_string`) and what it should become (`new_string`).
+//! Requiring `old_string` to be unique, unless `replace_all` is set, makes
+//! the model include enough context to pin down exactly one spot.
+
+use super::diff::unified_diff;
+use super::file_tracker::FileTracker;
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+pub struct EditFileTool {
+    tracker: FileTracker,
+    workspace: Workspace,
+}
+
+impl EditFileTool {
+    pub fn new(tracker: FileTracker, workspace: Workspace) -> Self {
+        Self { tracker, workspace }
+    }
+}
+
+impl ToolExecutor for EditFileTool {
+    fn name(&self) -> &str {
+        "edit_file"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "edit_file",
+            "Replace exact text in a file. old_string must match the file exactly \
+             (including indentation) and be unique unless replace_all is true. \
+             The file must have been read with read_file first. Returns a diff.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the file to edit"
+                    },
+                    "old_string": {
+                        "type": "string",
+                        "description": "Exact text to replace"
+                    },
+                    "new_string": {
+                        "type": "string",
+                        "description": "Text to replace it with"
+                    },
+                    "replace_all": {
+                        "type": "boolean",
+                        "description": "Replace every occurrence instead of requiring a unique match (default false)"
+                    }
+                },
+                "required": ["path", "old_string", "new_string"]
+            }),
+        )
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        input["path"].as_str().map(str::to_string).into_iter().collect()
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let path_str = input["path"]
+            .as_str()
+            .ok_or("Missing required parameter: path")?;
+        let old_string = input["old_string"]
+            .as_str()
+            .ok_or("Missing required parameter: old_string")?;
+        let new_string = input["new_string"]
+            .as_str()
+            .ok_or("Missing required parameter: new_string")?;
+        let replace_all = input["replace_all"].as_bool().unwrap_or(false);
+
+        let path = Path::new(path_str);
+        self.workspace.check(path)?;
+        if old_string.is_empty() {
+            return Err("old_string must not be empty; use write_file to create a file".to_string());
+        }
+        if old_string == new_string {
+            return Err("old_string and new_string are identical; nothing to change".to_string());
+        }
+
+        self.tracker.check_fresh(path)?;
+
+        let original = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let updated = match original.matches(old_string).count() {
+            0 => {
+                return Err(format!(
+                    "old_string not found in {}; check whitespace and indentation",
+                    path.display()
+                ));
+            }
+            1 => original.replacen(old_string, new_string, 1),
+            _ if replace_all => original.replace(old_string, new_string),
+            n => {
+                return Err(format!(
+                    "old_string appears {} times in {}; include more surrounding context \
+                     to make it unique, or set replace_all to true",
+                    n,
+                    path.display()
+                ));
+            }
+        };
+
+        fs::write(path, &updated).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        // The model knows the new contents from the diff
+        self.tracker.record(path);
+
+        Ok(ToolOutput::text(unified_diff(path_str, &original, &updated)))
+    }
+
+    fn echo_output(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,55 @@
+//! File Tracker - which files the agent has read this session
+//!
+//! Editing a file the model has never looked at means it is guessing at the
+//! contents. The read tool records every file it returns (with the file's
+//! modification time), and the edit/write tools refuse to touch an existing
+//! file unless it was read, and hasn't changed on disk since.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Shared record of files read in this session (cheap to clone)
+#[derive(Clone, Default)]
+pub struct FileTracker {
+    seen: Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>,
+}
+
+impl FileTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember that the model now knows the current contents of `path`
+    pub fn record(&self, path: &Path) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.insert(key(path), modified(path));
+    }
+
+    /// Ok if `path` was read and is unchanged since, Err explaining why not
+    pub fn check_fresh(&self, path: &Path) -> Result<(), String> {
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        match seen.get(&key(path)) {
+            None => Err(format!(
+                "{} has not been read in this session; read it with read_file before changing it",
+                path.display()
+            )),
+            Some(recorded) if *recorded != modified(path) => Err(format!(
+                "{} was modified since it was last read; read it again before changing it",
+                path.display()
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// Canonical path where possible, so "./a.rs" and "a.rs" are the same file
+fn key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
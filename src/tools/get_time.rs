@@ -1,11 +1,18 @@
-/// Get Current Time Tool
-///
-/// A simple tool that returns the current date and time.
-/// This demonstrates the ToolExecutor pattern without any risky operations.
+//! Get Current Time Tool
+//!
+//! Returns the current date and time, and answers the questions that
+//! usually follow: what time is it in another zone, what is this timestamp
+//! in Tokyo, what date is 90 days from now.
+//!
+//! Zone data comes from the system zoneinfo database (see `crate::time`),
+//! so there is no chrono dependency and no bundled tz table to go stale.
 
-use super::ToolExecutor;
-use crate::api::Tool;
-use serde_json::{json, Value};
+use super::{ToolOutput, TypedTool};
+use crate::time::{self, TimeZone};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+const SECS_PER_DAY: i64 = 86_400;
 
 pub struct GetTimeTool;
 
@@ -21,35 +28,126 @@ impl Default for GetTimeTool {
     }
 }
 
-impl ToolExecutor for GetTimeTool {
+#[derive(Deserialize, JsonSchema)]
+pub struct TimeInput {
+    /// IANA time zone to show the result in, e.g. "Europe/Berlin" (default: the system's local zone)
+    timezone: Option<String>,
+    /// ISO 8601 date/time to use instead of now, e.g. "2026-03-08T09:30" or "2026-03-08T09:30:00-05:00"
+    datetime: Option<String>,
+    /// IANA zone that `datetime` is in when it has no offset (default: the local zone)
+    from_timezone: Option<String>,
+    /// Duration to add; use negative values to subtract. Years, months, weeks and days follow the calendar in the target zone; hours, minutes and seconds are exact.
+    add: Option<Duration>,
+}
+
+#[derive(Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct Duration {
+    years: i64,
+    months: i64,
+    weeks: i64,
+    days: i64,
+    hours: i64,
+    minutes: i64,
+    seconds: i64,
+}
+
+impl TypedTool for GetTimeTool {
+    type Input = TimeInput;
+
     fn name(&self) -> &str {
         "get_current_time"
     }
 
-    fn definition(&self) -> Tool {
-        Tool::new(
-            "get_current_time",
-            "Get the current date and time. Use this when the user asks about the current time or date.",
-            json!({
-                "type": "object",
-                "properties": {},
-                "required": []
-            }),
-        )
+    fn description(&self) -> &str {
+        "Get the current date and time (ISO 8601, local and UTC). Can also show the time in \
+         any IANA time zone, convert a given date/time between zones, and add or subtract \
+         a duration. Use this instead of guessing dates or doing calendar math by hand."
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn run(&self, input: TimeInput) -> Result<ToolOutput, String> {
+        let local = TimeZone::local();
+        let zone = match &input.timezone {
+            Some(name) => TimeZone::named(name)?,
+            None => local.clone(),
+        };
+
+        // The starting instant: now, or the given date/time
+        let start = match &input.datetime {
+            Some(text) => {
+                let parsed = time::parse_iso8601(text)
+                    .ok_or_else(|| format!("Could not parse datetime (expected ISO 8601): {}", text))?;
+                match parsed.offset {
+                    Some(offset) => parsed.local_secs - offset,
+                    None => {
+                        let from = match &input.from_timezone {
+                            Some(name) => TimeZone::named(name)?,
+                            None => local.clone(),
+                        };
+                        from.to_utc(parsed.local_secs)
+                    }
+                }
+            }
+            None => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_err(|e| e.to_string())?;
+                now.as_secs() as i64
+            }
+        };
+
+        let result = match &input.add {
+            Some(add) => add_duration(&zone, start, add),
+            None => start,
+        };
+
+        let mut output = String::new();
+        if result != start {
+            output.push_str(&format!("Input:  {}\n", describe(&zone, start)));
+            output.push_str(&format!("Result: {}\n", describe(&zone, result)));
+        } else {
+            output.push_str(&format!("Time:   {}\n", describe(&zone, result)));
+        }
+        if zone.name != local.name {
+            output.push_str(&format!("Local:  {}\n", describe(&local, result)));
+        }
+        output.push_str(&format!("UTC:    {}\n", time::format_iso8601(result, 0)));
+        output.push_str(&format!("Unix timestamp: {}", result));
+        Ok(ToolOutput::text(output))
     }
+}
 
-    fn execute(&self, _input: Value) -> Result<String, String> {
-        // Get current time using std (no external crate needed)
-        let now = std::time::SystemTime::now();
-        let duration = now
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|e| e.to_string())?;
-
-        // Convert to readable format (basic, no chrono dependency)
-        let secs = duration.as_secs();
-        Ok(format!(
-            "Current Unix timestamp: {} seconds since epoch",
-            secs
-        ))
+/// "2026-10-18T14:03:12+02:00 Sunday (Europe/Berlin, CEST)"
+fn describe(zone: &TimeZone, utc_secs: i64) -> String {
+    let offset = zone.offset_at(utc_secs);
+    let local_days = (utc_secs + offset.offset).div_euclid(SECS_PER_DAY);
+    format!(
+        "{} {} ({}, {})",
+        time::format_iso8601(utc_secs, offset.offset),
+        time::weekday_name(local_days),
+        zone.name,
+        offset.abbreviation
+    )
+}
+
+/// Calendar units move the wall clock in `zone`; clock units move the instant
+fn add_duration(zone: &TimeZone, utc_secs: i64, add: &Duration) -> i64 {
+    let months = add.years * 12 + add.months;
+    let days = add.weeks * 7 + add.days;
+
+    let mut result = utc_secs;
+    if months != 0 || days != 0 {
+        let local = utc_secs + zone.offset_at(utc_secs).offset;
+        let clock = local.rem_euclid(SECS_PER_DAY);
+        let (year, month, day) = time::civil_from_days(local.div_euclid(SECS_PER_DAY));
+        let (year, month, day) = time::add_months(year, month, day, months);
+        let shifted = (time::days_from_civil(year, month, day) + days) * SECS_PER_DAY + clock;
+        result = zone.to_utc(shifted);
     }
+
+    result + add.hours * 3600 + add.minutes * 60 + add.seconds
 }
@@ -0,0 +1,470 @@
+//! Git Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Version control through a structured interface instead of free-form
+//! shell: the model picks an operation and the tool runs `git` with fixed
+//! arguments, then condenses the output (status grouped by state, one-line
+//! logs, truncated diffs).
+//!
+//! Read-only operations run immediately. Operations that change the
+//! repository (add, commit, creating a branch) go through the permission
+//! rules, so by default the user is asked first (see `permissions`).
+//!
+//! Paths, including the one in a `rev:path` ref, go through the
+//! `Workspace` like any file tool's, and the patches `diff` and `show`
+//! print leave out files the workspace hides, so git can't show a secret
+//! file or one outside the workspace. Pathspecs are literal (no globs or
+//! `:(magic)`).
+
+use super::{Sandbox, ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Cap on what any single operation returns
+const MAX_OUTPUT_CHARS: usize = 30_000;
+
+/// Commits shown by `log` when the model doesn't say
+const DEFAULT_LOG_COUNT: u64 = 20;
+
+/// Operations that only read the repository
+const READ_ONLY_OPERATIONS: &[&str] = &["status", "diff", "log", "show", "blame", "branches"];
+
+pub struct GitTool {
+    workspace: Workspace,
+    sandbox: Option<Sandbox>,
+}
+
+impl GitTool {
+    pub fn new(workspace: Workspace) -> Self {
+        Self {
+            workspace,
+            sandbox: None,
+        }
+    }
+
+    /// Run git, and with it hooks and diff drivers, inside `sandbox`
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Refuse a `rev:path` ref whose file the workspace doesn't allow
+    fn check_ref(&self, git_ref: &str) -> Result<(), String> {
+        let Some(path) = ref_path(git_ref) else {
+            return Ok(());
+        };
+        // "rev:./file" is relative to the current directory, "rev:file" to the repository root
+        if path.starts_with("./") || path.starts_with("../") {
+            return self.workspace.check(Path::new(path));
+        }
+        self.workspace.check(&self.toplevel()?.join(path))
+    }
+
+    fn toplevel(&self) -> Result<PathBuf, String> {
+        Ok(PathBuf::from(self.git(&["rev-parse", "--show-toplevel"])?.trim()))
+    }
+
+    /// `patch` with the diffs of files the workspace hides replaced by a note
+    fn hide_files(&self, patch: String) -> Result<String, String> {
+        if !patch.contains("diff --git ") {
+            return Ok(patch);
+        }
+        let top = self.toplevel()?;
+        let mut shown = String::with_capacity(patch.len());
+        let mut hiding = false;
+        for line in patch.split_inclusive('\n') {
+            if let Some(header) = line.strip_prefix("diff --git ") {
+                let (old, new) = diff_paths(header.trim_end());
+                hiding = [old, new].iter().any(|path| !self.workspace.permits(&top.join(path)));
+                if hiding {
+                    shown.push_str(&format!(
+                        "[diff of {} left out: the file may contain secrets or is outside the workspace]\n",
+                        new
+                    ));
+                }
+            }
+            if !hiding {
+                shown.push_str(line);
+            }
+        }
+        Ok(shown)
+    }
+
+    /// `git status` grouped into branch / staged / unstaged / untracked
+    fn status(&self) -> Result<ToolOutput, String> {
+        let raw = self.git(&["status", "--porcelain=v1", "--branch"])?;
+
+        let mut branch = String::new();
+        let mut staged = Vec::new();
+        let mut unstaged = Vec::new();
+        let mut untracked = Vec::new();
+
+        for line in raw.lines() {
+            if let Some(info) = line.strip_prefix("## ") {
+                branch = info.to_string();
+                continue;
+            }
+            if line.len() < 4 {
+                continue;
+            }
+            let (code, file) = line.split_at(3);
+            let mut flags = code.chars();
+            let (index, worktree) = (flags.next().unwrap_or(' '), flags.next().unwrap_or(' '));
+
+            if index == '?' {
+                untracked.push(file.to_string());
+                continue;
+            }
+            if index != ' ' {
+                staged.push(format!("{} {}", status_word(index), file));
+            }
+            if worktree != ' ' {
+                unstaged.push(format!("{} {}", status_word(worktree), file));
+            }
+        }
+
+        let mut output = format!("Branch: {}\n", branch);
+        for (title, entries) in [("Staged", staged), ("Unstaged", unstaged), ("Untracked", untracked)] {
+            if !entries.is_empty() {
+                output.push_str(&format!("\n{} ({}):\n", title, entries.len()));
+                for entry in entries {
+                    output.push_str(&format!("  {}\n", entry));
+                }
+            }
+        }
+        if output.lines().count() == 1 {
+            output.push_str("Working tree clean\n");
+        }
+        Ok(truncate(output))
+    }
+
+    /// Run git and return stdout, or stderr as the error
+    fn git(&self, args: &[&str]) -> Result<String, String> {
+        let mut git = Command::new("git");
+        git.args(args)
+            // Paths name files, never globs or pathspec magic like ":(top)"
+            .env("GIT_LITERAL_PATHSPECS", "1");
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(&mut git);
+        }
+        let output = git.output().map_err(|e| format!("Failed to run git: {}", e))?;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(format!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+impl Default for GitTool {
+    fn default() -> Self {
+        Self::new(Workspace::default())
+    }
+}
+
+impl ToolExecutor for GitTool {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "git",
+            "Inspect or update the git repository in the current directory. \
+             Read-only: status, diff, log, show, blame, branches. \
+             Mutating (may need the user's approval): add, commit, create_branch.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "operation": {
+                        "type": "string",
+                        "enum": ["status", "diff", "log", "show", "blame", "branches", "add", "commit", "create_branch"],
+                        "description": "The git operation to run"
+                    },
+                    "ref": {
+                        "type": "string",
+                        "description": "Commit, branch or tag: diff against it, show it, or start log/create_branch from it"
+                    },
+                    "staged": {
+                        "type": "boolean",
+                        "description": "diff: show staged changes instead of unstaged ones"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "diff/log: limit to this path. blame: the file to blame (required)"
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "blame: first line of the range"
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "blame: last line of the range"
+                    },
+                    "max_count": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "log: number of commits (default 20)"
+                    },
+                    "paths": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "add: files to stage"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "commit: the commit message"
+                    },
+                    "branch": {
+                        "type": "string",
+                        "description": "create_branch: name of the new branch"
+                    }
+                },
+                "required": ["operation"]
+            }),
+        )
+    }
+
+    fn is_read_only(&self, input: &Value) -> bool {
+        input["operation"]
+            .as_str()
+            .is_some_and(|op| READ_ONLY_OPERATIONS.contains(&op))
+    }
+
+    /// The operation and what it touches: `add src/a.rs`, `create_branch fix`
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        let Some(operation) = input["operation"].as_str() else {
+            return Vec::new();
+        };
+        let mut target = vec![operation];
+        match operation {
+            "add" => target.extend(
+                input["paths"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str),
+            ),
+            "create_branch" => target.extend(input["branch"].as_str()),
+            _ => {}
+        }
+        vec![target.join(" ")]
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let operation = input["operation"]
+            .as_str()
+            .ok_or("Missing required parameter: operation")?;
+
+        let git_ref = input["ref"].as_str().map(checked_arg).transpose()?;
+        if let Some(git_ref) = git_ref {
+            self.check_ref(git_ref)?;
+        }
+        let path = input["path"].as_str();
+        if let Some(path) = path {
+            self.workspace.check(Path::new(path))?;
+        }
+
+        match operation {
+            "status" => self.status(),
+
+            "diff" => {
+                let mut args = vec!["diff", "--no-color"];
+                if input["staged"].as_bool().unwrap_or(false) {
+                    args.push("--staged");
+                }
+                args.extend(git_ref);
+                with_path(&mut args, path);
+
+                // Summary first, so a truncated patch still shows the scope
+                let mut stat_args = args.clone();
+                stat_args.insert(1, "--stat");
+                let stat = self.git(&stat_args)?;
+                let patch = self.hide_files(self.git(&args)?)?;
+                if patch.trim().is_empty() {
+                    return Ok(ToolOutput::text("No differences"));
+                }
+                Ok(truncate(format!("{}\n{}", stat.trim_end(), patch)))
+            }
+
+            "log" => {
+                let count = format!(
+                    "--max-count={}",
+                    input["max_count"].as_u64().unwrap_or(DEFAULT_LOG_COUNT)
+                );
+                let mut args = vec![
+                    "log",
+                    count.as_str(),
+                    "--date=short",
+                    "--pretty=format:%h  %ad  %<(18,trunc)%an  %s",
+                ];
+                args.extend(git_ref);
+                with_path(&mut args, path);
+                Ok(truncate(self.git(&args)?))
+            }
+
+            "show" => {
+                let git_ref = git_ref.unwrap_or("HEAD");
+                let shown = self.git(&["show", "--no-color", "--stat", "--patch", git_ref])?;
+                Ok(truncate(self.hide_files(shown)?))
+            }
+
+            "blame" => {
+                let path = path.ok_or("blame needs a path")?;
+                let mut args = vec!["blame", "--date=short"];
+                let range;
+                if let Some(start) = input["start_line"].as_u64() {
+                    let end = input["end_line"].as_u64().unwrap_or(start);
+                    range = format!("-L{},{}", start, end);
+                    args.push(&range);
+                }
+                args.push("--");
+                args.push(path);
+                Ok(truncate(self.git(&args)?))
+            }
+
+            "branches" => Ok(truncate(self.git(&[
+                "branch",
+                "--all",
+                "--format=%(HEAD) %(refname:short)  %(objectname:short)  %(upstream:track)  %(contents:subject)",
+            ])?)),
+
+            "add" => {
+                let paths: Vec<&str> = input["paths"]
+                    .as_array()
+                    .map(|a| a.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+                if paths.is_empty() {
+                    return Err("add needs a non-empty paths list".to_string());
+                }
+                for path in &paths {
+                    self.workspace.check(Path::new(path))?;
+                }
+
+                let mut args = vec!["add", "--"];
+                args.extend(&paths);
+                self.git(&args)?;
+                self.status()
+            }
+
+            "commit" => {
+                let message = input["message"]
+                    .as_str()
+                    .filter(|m| !m.trim().is_empty())
+                    .ok_or("commit needs a message")?;
+
+                let staged = self.git(&["diff", "--staged", "--stat"])?;
+                if staged.trim().is_empty() {
+                    return Err("Nothing is staged; use add first".to_string());
+                }
+
+                self.git(&["commit", "--quiet", "-m", message])?;
+                self.git(&["log", "-1", "--stat", "--no-color"]).map(ToolOutput::text)
+            }
+
+            "create_branch" => {
+                let branch = checked_arg(
+                    input["branch"]
+                        .as_str()
+                        .ok_or("create_branch needs a branch name")?,
+                )?;
+
+                let mut args = vec!["checkout", "-b", branch];
+                args.extend(git_ref);
+                self.git(&args)?;
+                Ok(ToolOutput::text(format!("Switched to new branch '{}'", branch)))
+            }
+
+            other => Err(format!("Unknown git operation: {}", other)),
+        }
+    }
+}
+
+fn status_word(code: char) -> &'static str {
+    match code {
+        'M' => "modified:",
+        'A' => "added:   ",
+        'D' => "deleted: ",
+        'R' => "renamed: ",
+        'C' => "copied:  ",
+        'U' => "conflict:",
+        _ => "changed: ",
+    }
+}
+
+/// The file a `rev:path` ref names, parsed the way git does: `:path` and
+/// `:2:path` are in the index, colons inside `@{...}` or `^{...}` belong to
+/// the revision, and `:/text` searches commit messages
+fn ref_path(git_ref: &str) -> Option<&str> {
+    if git_ref.starts_with(":/") {
+        return None;
+    }
+    if let Some(rest) = git_ref.strip_prefix(':') {
+        let staged = rest.len() > 2 && rest.as_bytes()[1] == b':' && (b'0'..=b'3').contains(&rest.as_bytes()[0]);
+        return Some(if staged { &rest[2..] } else { rest });
+    }
+    let mut depth = 0;
+    for (i, c) in git_ref.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ':' if depth == 0 => return Some(&git_ref[i + 1..]),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The old and new paths from the `a/x b/y` of a `diff --git` header
+fn diff_paths(header: &str) -> (&str, &str) {
+    // Unusual names come quoted: "a/na\303\257ve" "b/na\303\257ve"
+    let (old, new) = header
+        .rsplit_once(" \"b/")
+        .or_else(|| header.rsplit_once(" b/"))
+        .unwrap_or((header, header));
+    let old = old.trim_matches('"');
+    (old.strip_prefix("a/").unwrap_or(old), new.trim_end_matches('"'))
+}
+
+fn with_path<'a>(args: &mut Vec<&'a str>, path: Option<&'a str>) {
+    if let Some(path) = path {
+        args.push("--");
+        args.push(path);
+    }
+}
+
+/// Refs and branch names must not be mistaken for options
+fn checked_arg(value: &str) -> Result<&str, String> {
+    if value.starts_with('-') {
+        Err(format!("Invalid ref or branch name: {}", value))
+    } else {
+        Ok(value)
+    }
+}
+
+fn truncate(text: String) -> ToolOutput {
+    if text.len() <= MAX_OUTPUT_CHARS {
+        return ToolOutput::text(text);
+    }
+    let mut cut = MAX_OUTPUT_CHARS;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    ToolOutput::text(format!(
+        "{}\n\n[output truncated: showing {} of {} bytes; narrow it with a path or ref]",
+        &text[..cut],
+        cut,
+        text.len()
+    ))
+    .truncated(true)
+}
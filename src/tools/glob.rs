@@ -0,0 +1,128 @@
+//! Glob Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Finds files by name pattern. Results are sorted by modification time,
+//! newest first, because recently touched files are usually the ones the
+//! user is asking about.
+
+use super::walk::{self, WalkFilter};
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use globset::GlobBuilder;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Paths returned at most
+const MAX_RESULTS: usize = 200;
+
+pub struct GlobTool {
+    workspace: Workspace,
+}
+
+impl GlobTool {
+    pub fn new(workspace: Workspace) -> Self {
+        Self { workspace }
+    }
+}
+
+impl Default for GlobTool {
+    fn default() -> Self {
+        Self::new(Workspace::default())
+    }
+}
+
+impl ToolExecutor for GlobTool {
+    fn name(&self) -> &str {
+        "glob"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "glob",
+            "Find files whose path matches a glob pattern such as \"**/*.rs\" or \"src/**/mod.rs\". \
+             Respects .gitignore. Results are sorted by modification time, newest first.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Glob relative to path; * stays within a directory, ** crosses directories"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to search from (default: current directory)"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        )
+    }
+
+    fn is_read_only(&self, _input: &Value) -> bool {
+        true
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        vec![input["path"].as_str().unwrap_or(".").to_string()]
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let pattern = input["pattern"]
+            .as_str()
+            .ok_or("Missing required parameter: pattern")?;
+        let root = Path::new(input["path"].as_str().unwrap_or("."));
+        self.workspace.check(root)?;
+        let filter = WalkFilter {
+            workspace: Some(&self.workspace),
+            ..WalkFilter::default()
+        };
+
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid glob '{}': {}", pattern, e))?
+            .compile_matcher();
+
+        let mut found: Vec<(SystemTime, String)> = walk::walker(root, &filter)?
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .is_ok_and(|relative| matcher.is_match(relative))
+            })
+            .map(|entry| {
+                let modified = entry
+                    .metadata()
+                    .ok()
+                    .and_then(|m| m.modified().ok())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (modified, walk::display_path(entry.path()))
+            })
+            .collect();
+
+        if found.is_empty() {
+            return Ok(ToolOutput::text(format!("No files match {}", pattern)));
+        }
+
+        found.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        let total = found.len();
+
+        let mut output: String = found
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, path)| path + "\n")
+            .collect();
+        if total > MAX_RESULTS {
+            output.push_str(&format!(
+                "\n[{} of {} files shown; narrow the pattern to see the rest]",
+                MAX_RESULTS, total
+            ));
+        }
+        Ok(ToolOutput::text(output).truncated(total > MAX_RESULTS))
+    }
+}
@@ -0,0 +1,221 @@
+//! Grep Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Regex search across the workspace, so the model can find definitions
+//! and call sites instead of reading files one by one. Output follows
+//! grep's conventions: `path:line:text` for matches, `path-line-text` for
+//! context lines, and `--` between separate groups.
+
+use super::walk::{self, WalkFilter};
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use regex::RegexBuilder;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// Matches returned when the model doesn't set max_matches
+const DEFAULT_MAX_MATCHES: usize = 100;
+
+/// Long lines (minified code) are cut to this many characters
+const MAX_LINE_CHARS: usize = 500;
+
+pub struct GrepTool {
+    workspace: Workspace,
+}
+
+impl GrepTool {
+    pub fn new(workspace: Workspace) -> Self {
+        Self { workspace }
+    }
+}
+
+impl Default for GrepTool {
+    fn default() -> Self {
+        Self::new(Workspace::default())
+    }
+}
+
+impl ToolExecutor for GrepTool {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "grep",
+            "Search file contents with a regular expression. Respects .gitignore and skips \
+             binary and very large files. Returns matching lines as path:line:text.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression (Rust regex syntax)"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory to search (default: current directory)"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Only search files matching this glob, e.g. \"*.rs\""
+                    },
+                    "type": {
+                        "type": "string",
+                        "description": "Only search files of this type, e.g. \"rust\", \"py\", \"js\""
+                    },
+                    "case_insensitive": {
+                        "type": "boolean",
+                        "description": "Ignore case (default false)"
+                    },
+                    "context": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Lines of context to show around each match (default 0)"
+                    },
+                    "max_matches": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Stop after this many matching lines (default 100)"
+                    },
+                    "files_only": {
+                        "type": "boolean",
+                        "description": "Only list the files that contain a match (default false)"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        )
+    }
+
+    fn is_read_only(&self, _input: &Value) -> bool {
+        true
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        vec![input["path"].as_str().unwrap_or(".").to_string()]
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let pattern = input["pattern"]
+            .as_str()
+            .ok_or("Missing required parameter: pattern")?;
+        let root = input["path"].as_str().unwrap_or(".");
+        let context = input["context"].as_u64().unwrap_or(0) as usize;
+        let max_matches = input["max_matches"]
+            .as_u64()
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_MAX_MATCHES)
+            .max(1);
+        let files_only = input["files_only"].as_bool().unwrap_or(false);
+
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(input["case_insensitive"].as_bool().unwrap_or(false))
+            .build()
+            .map_err(|e| format!("Invalid regex: {}", e))?;
+
+        self.workspace.check(Path::new(root))?;
+        let filter = WalkFilter {
+            glob: input["glob"].as_str(),
+            file_type: input["type"].as_str(),
+            workspace: Some(&self.workspace),
+            ..WalkFilter::default()
+        };
+
+        let mut output = String::new();
+        let mut matches = 0;
+        let mut files_matched = 0;
+        let mut skipped = 0;
+
+        for entry in walk::walker(Path::new(root), &filter)?.build().flatten() {
+            if matches >= max_matches {
+                break;
+            }
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+
+            let path = entry.path();
+            if entry.metadata().map(|m| m.len()).unwrap_or(0) > walk::MAX_SEARCH_BYTES {
+                skipped += 1;
+                continue;
+            }
+            let Ok(bytes) = fs::read(path) else { continue };
+            if walk::is_binary(&bytes) {
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(&bytes);
+            let lines: Vec<&str> = text.lines().collect();
+            let hits: Vec<usize> = lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| regex.is_match(line))
+                .map(|(i, _)| i)
+                .take(max_matches - matches)
+                .collect();
+
+            if hits.is_empty() {
+                continue;
+            }
+            files_matched += 1;
+            matches += hits.len();
+
+            let shown = walk::display_path(path);
+            if files_only {
+                output.push_str(&shown);
+                output.push('\n');
+                continue;
+            }
+            format_hits(&mut output, &shown, &lines, &hits, context);
+        }
+
+        if matches == 0 {
+            return Ok(ToolOutput::text(format!("No matches for /{}/", pattern)));
+        }
+
+        output.push_str(&format!("\n[{} matching lines in {} files", matches, files_matched));
+        if matches >= max_matches {
+            output.push_str(&format!("; stopped at max_matches={}", max_matches));
+        }
+        if skipped > 0 {
+            output.push_str(&format!("; {} files over {} bytes skipped", skipped, walk::MAX_SEARCH_BYTES));
+        }
+        output.push(']');
+        Ok(ToolOutput::text(output).truncated(matches >= max_matches || skipped > 0))
+    }
+}
+
+/// Append one file's matches (with context lines) in grep format
+fn format_hits(output: &mut String, path: &str, lines: &[&str], hits: &[usize], context: usize) {
+    let hit_set: BTreeSet<usize> = hits.iter().copied().collect();
+    let shown: BTreeSet<usize> = hits
+        .iter()
+        .flat_map(|&i| i.saturating_sub(context)..=(i + context).min(lines.len() - 1))
+        .collect();
+
+    let mut previous: Option<usize> = None;
+    for i in shown {
+        if context > 0 && previous.is_some_and(|p| i > p + 1) {
+            output.push_str("--\n");
+        }
+        let separator = if hit_set.contains(&i) { ':' } else { '-' };
+        output.push_str(&format!("{}{}{}{}{}\n", path, separator, i + 1, separator, truncate(lines[i])));
+        previous = Some(i);
+    }
+    if context > 0 {
+        output.push_str("--\n");
+    }
+}
+
+fn truncate(line: &str) -> String {
+    if line.chars().count() > MAX_LINE_CHARS {
+        let cut: String = line.chars().take(MAX_LINE_CHARS).collect();
+        format!("{}...", cut)
+    } else {
+        line.to_string()
+    }
+}
@@ -0,0 +1,221 @@
+//! List Directory Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Gives the model a map of an unfamiliar project in one call: an indented
+//! tree with file sizes, cut off at a depth limit. Ignored paths (.gitignore,
+//! .ignore and the user's configured globs) are left out, and directories
+//! with a huge number of entries are summarized rather than listed in full.
+
+use super::walk::{self, WalkFilter};
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Depth used when the model doesn't ask for one
+const DEFAULT_DEPTH: usize = 3;
+
+/// Deepest tree the model may ask for
+const MAX_DEPTH: usize = 10;
+
+/// Entries shown per directory before summarizing the rest
+const MAX_ENTRIES_PER_DIR: usize = 50;
+
+/// Entries shown in the whole tree before summarizing the rest
+const MAX_TOTAL_ENTRIES: usize = 1000;
+
+/// One walked entry, grouped under its parent directory
+struct Entry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    size: u64,
+}
+
+pub struct ListDirectoryTool {
+    /// Extra globs to leave out, on top of the ignore files
+    ignore_globs: Vec<String>,
+    workspace: Workspace,
+}
+
+impl ListDirectoryTool {
+    pub fn new(ignore_globs: Vec<String>, workspace: Workspace) -> Self {
+        Self {
+            ignore_globs,
+            workspace,
+        }
+    }
+}
+
+impl ToolExecutor for ListDirectoryTool {
+    fn name(&self) -> &str {
+        "list_directory"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "list_directory",
+            "Show the tree of a directory with file sizes, skipping ignored files. \
+             Use this first to get oriented in a project.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to list (default: current directory)"
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 10,
+                        "description": "How many levels deep to list (default 3, max 10)"
+                    }
+                },
+                "required": []
+            }),
+        )
+    }
+
+    fn is_read_only(&self, _input: &Value) -> bool {
+        true
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        vec![input["path"].as_str().unwrap_or(".").to_string()]
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let root = Path::new(input["path"].as_str().unwrap_or("."));
+        let depth = input["depth"]
+            .as_u64()
+            .map(|d| d as usize)
+            .unwrap_or(DEFAULT_DEPTH)
+            .clamp(1, MAX_DEPTH);
+
+        self.workspace.check(root)?;
+        if !root.is_dir() {
+            return Err(format!("{} is not a directory", root.display()));
+        }
+
+        let filter = WalkFilter {
+            exclude: &self.ignore_globs,
+            workspace: Some(&self.workspace),
+            ..WalkFilter::default()
+        };
+        let mut builder = walk::walker(root, &filter)?;
+        builder.max_depth(Some(depth));
+
+        // Group entries by parent directory (the walk is sorted by name)
+        let mut children: BTreeMap<PathBuf, Vec<Entry>> = BTreeMap::new();
+        for entry in builder.build().flatten() {
+            if entry.depth() == 0 {
+                continue;
+            }
+            let Some(parent) = entry.path().parent() else { continue };
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            children.entry(parent.to_path_buf()).or_default().push(Entry {
+                path: entry.path().to_path_buf(),
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir,
+                size: if is_dir { 0 } else { entry.metadata().map(|m| m.len()).unwrap_or(0) },
+            });
+        }
+
+        // Directories first, then files, each alphabetically
+        for entries in children.values_mut() {
+            entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        }
+
+        let mut output = format!("{}/\n", walk::display_path(root));
+        let mut budget = MAX_TOTAL_ENTRIES;
+        let mut elided = false;
+        render(&children, root, 1, &mut budget, &mut elided, &mut output);
+
+        if budget == 0 {
+            output.push_str(&format!(
+                "\n[output limited to {} entries; list a subdirectory or reduce depth]",
+                MAX_TOTAL_ENTRIES
+            ));
+        }
+        Ok(ToolOutput::text(output).truncated(elided || budget == 0))
+    }
+}
+
+/// Append the entries of `dir` (and recursively their children)
+fn render(
+    children: &BTreeMap<PathBuf, Vec<Entry>>,
+    dir: &Path,
+    level: usize,
+    budget: &mut usize,
+    elided: &mut bool,
+    output: &mut String,
+) {
+    let Some(entries) = children.get(dir) else { return };
+    let indent = "  ".repeat(level);
+
+    let shown = entries.len().min(MAX_ENTRIES_PER_DIR).min(*budget);
+    for entry in &entries[..shown] {
+        *budget -= 1;
+        if entry.is_dir {
+            output.push_str(&format!("{}{}/\n", indent, entry.name));
+            render(children, &entry.path, level + 1, budget, elided, output);
+        } else {
+            output.push_str(&format!("{}{} ({})\n", indent, entry.name, format_size(entry.size)));
+        }
+    }
+
+    // Summarize what didn't fit instead of silently dropping it
+    let rest = &entries[shown..];
+    if !rest.is_empty() {
+        *elided = true;
+        let dirs = rest.iter().filter(|e| e.is_dir).count();
+        let files = rest.len() - dirs;
+        let mut parts = Vec::new();
+        if files > 0 {
+            parts.push(format!("{} more {}", with_commas(files), plural(files, "file")));
+        }
+        if dirs > 0 {
+            parts.push(format!("{} more {}", with_commas(dirs), plural(dirs, "directory")));
+        }
+        output.push_str(&format!("{}… {}\n", indent, parts.join(", ")));
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match (count, noun) {
+        (1, _) => noun.to_string(),
+        (_, "directory") => "directories".to_string(),
+        _ => format!("{}s", noun),
+    }
+}
+
+/// 4120 -> "4,120"
+fn with_commas(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Human-readable file size
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -1,27 +1,77 @@
-/// Tools module - executable capabilities for the agent
-///
-/// Topic 9: Designing a Tool System
-///
-/// Key concepts:
-/// - ToolExecutor trait: uniform interface for all tools
-/// - ToolRegistry: holds and looks up available tools
-/// - Each tool: definition (for Claude) + execution (actual work)
+//! Tools module - executable capabilities for the agent
+//!
+//! Topic 9: Designing a Tool System
+//!
+//! Key concepts:
+//! - ToolExecutor trait: uniform interface for all tools
+//! - TypedTool: a tool whose input is a struct, with the schema generated
+//!   from it (the preferred way to write new tools)
+//! - ToolRegistry: holds and looks up available tools, and runs each call
+//!   on its own thread with a timeout
+//! - Each tool: definition (for Claude) + execution (actual work)
 
+mod apply_patch;
+mod bash;
+mod cancel;
+mod cargo;
+mod diff;
+mod edit_file;
+mod file_tracker;
 mod get_time;
+mod git;
+mod glob;
+mod grep;
+mod list_dir;
+mod output;
+mod permissions;
+mod plugin;
+mod read_file;
 mod registry;
+mod sandbox;
+mod schema;
+mod spill;
+mod todo;
+mod typed;
+mod walk;
+mod workspace;
+mod write_file;
 
+pub use apply_patch::ApplyPatchTool;
+pub use bash::BashTool;
+pub use cancel::CancelToken;
+pub use cargo::CargoTool;
+pub use edit_file::EditFileTool;
+pub use file_tracker::FileTracker;
 pub use get_time::GetTimeTool;
+pub use git::GitTool;
+pub use glob::GlobTool;
+pub use grep::GrepTool;
+pub use list_dir::ListDirectoryTool;
+pub use output::ToolOutput;
+pub use permissions::{Approval, Decision, Permissions, Rule};
+pub use plugin::PluginTool;
+pub use read_file::ReadFileTool;
 pub use registry::ToolRegistry;
+pub use sandbox::Sandbox;
+pub use spill::Spill;
+pub use todo::{TodoItem, TodoList, TodoTool};
+pub use typed::TypedTool;
+pub use workspace::Workspace;
+pub use write_file::WriteFileTool;
 
 use crate::api::Tool;
 use serde_json::Value;
+use std::time::Duration;
+
+/// How long a call may take unless the tool says otherwise
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
 
 /// The core trait that all tools must implement
 ///
 /// This is the heart of the tool system. Every tool:
 /// 1. Has a name (for lookup when Claude requests it)
 /// 2. Provides a definition (sent to Claude so it knows what's available)
-/// 3. Can execute with JSON input and return a string result
+/// 3. Can execute with JSON input and return a `ToolOutput`
 pub trait ToolExecutor: Send + Sync {
     /// Unique name of the tool (must match what's sent to Claude)
     fn name(&self) -> &str;
@@ -31,5 +81,41 @@ pub trait ToolExecutor: Send + Sync {
 
     /// Execute the tool with the given input
     /// Returns Ok(output) on success, Err(error_message) on failure
-    fn execute(&self, input: Value) -> Result<String, String>;
+    /// (the registry turns the message into an is_error output)
+    fn execute(&self, input: Value) -> Result<ToolOutput, String>;
+
+    /// Execute, stopping early once `cancel` is set; tools that run for a
+    /// while override this (and have `execute` call it)
+    fn execute_cancellable(&self, input: Value, _cancel: &CancelToken) -> Result<ToolOutput, String> {
+        self.execute(input)
+    }
+
+    /// How long the registry waits for this call before cancelling it
+    fn timeout(&self, _input: &Value) -> Duration {
+        DEFAULT_TIMEOUT
+    }
+
+    /// Whether the user should see the output too (e.g. the diff of an edit)
+    fn echo_output(&self) -> bool {
+        false
+    }
+
+    /// Whether results over the output budget are spilled to a file (see
+    /// `spill`); tools that page through files themselves opt out
+    fn spill_output(&self) -> bool {
+        true
+    }
+
+    /// Whether this call only looks at things (files, the repository, the
+    /// clock); read-only calls run without asking the user, and alongside
+    /// each other when the model makes several at once
+    fn is_read_only(&self, _input: &Value) -> bool {
+        false
+    }
+
+    /// What this call acts on, for permission rules like `bash(git status*)`:
+    /// the command's parts for bash, the path for file tools
+    fn permission_targets(&self, _input: &Value) -> Vec<String> {
+        Vec::new()
+    }
 }
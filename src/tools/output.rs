@@ -0,0 +1,130 @@
+//! Tool Output - what a tool hands back
+//!
+//! Topic 9: Designing a Tool System
+//!
+//! A plain string can't say "this is an error message, not output", can't
+//! carry an image, and can't tell the UI how long the tool took. A
+//! `ToolOutput` has:
+//! - content blocks (text and images), sent to the model as the tool_result
+//! - `is_error`, sent as the tool_result's is_error flag
+//! - metadata (duration, size, whether output was cut short), only for us
+
+use crate::api::{ContentBlock, ImageSource, ToolResultContent};
+use base64::Engine;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct ToolOutput {
+    /// Text and Image blocks
+    pub blocks: Vec<ContentBlock>,
+    pub is_error: bool,
+    pub metadata: OutputMetadata,
+}
+
+/// Facts about a tool run for the UI; never sent to the model
+#[derive(Debug, Clone, Default)]
+pub struct OutputMetadata {
+    /// Wall time of the call (filled in by the registry)
+    pub duration: Option<Duration>,
+    /// Size of the content in bytes, images decoded (filled in by the registry)
+    pub bytes: usize,
+    /// The tool left part of its output out
+    pub truncated: bool,
+}
+
+impl ToolOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            blocks: vec![ContentBlock::Text { text: text.into() }],
+            is_error: false,
+            metadata: OutputMetadata::default(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            ..Self::text(message)
+        }
+    }
+
+    /// Append an image (raw bytes, e.g. the contents of a PNG file)
+    pub fn with_image(mut self, media_type: &str, bytes: &[u8]) -> Self {
+        self.blocks.push(ContentBlock::Image {
+            source: ImageSource {
+                source_type: "base64".to_string(),
+                media_type: media_type.to_string(),
+                data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            },
+        });
+        self
+    }
+
+    /// Append a text block, e.g. a note about how the call ended
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.blocks.push(ContentBlock::Text { text: text.into() });
+        self
+    }
+
+    /// Mark whether the tool had to leave part of its output out
+    pub fn truncated(mut self, truncated: bool) -> Self {
+        self.metadata.truncated = truncated;
+        self
+    }
+
+    /// The text blocks joined, with a placeholder for each image
+    pub fn text_content(&self) -> String {
+        ToolResultContent::Blocks(self.blocks.clone()).to_text()
+    }
+
+    /// Content size in bytes, counting images by their decoded size
+    pub fn size(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text } => text.len(),
+                ContentBlock::Image { source } => source.data.len() / 4 * 3,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The tool_result block answering the tool_use with this id
+    pub fn into_result(self, tool_use_id: String) -> ContentBlock {
+        // A lone text block goes as a plain string, the compact form
+        let content = match self.blocks.as_slice() {
+            [ContentBlock::Text { text }] => ToolResultContent::Text(text.clone()),
+            _ => ToolResultContent::Blocks(self.blocks),
+        };
+        ContentBlock::ToolResult {
+            tool_use_id,
+            content,
+            is_error: self.is_error,
+        }
+    }
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self::text(text)
+    }
+}
+
+impl OutputMetadata {
+    /// "0.4s, 12.3 KB, truncated"
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(duration) = self.duration {
+            parts.push(format!("{:.1}s", duration.as_secs_f64()));
+        }
+        parts.push(if self.bytes < 1024 {
+            format!("{} B", self.bytes)
+        } else {
+            format!("{:.1} KB", self.bytes as f64 / 1024.0)
+        });
+        if self.truncated {
+            parts.push("truncated".to_string());
+        }
+        parts.join(", ")
+    }
+}
@@ -0,0 +1,269 @@
+//! Permissions - who decides whether a tool call runs
+//!
+//! Topic 17: Safety and Sandboxing
+//!
+//! Every call passes through here before the tool sees it. The decision is
+//! the first of:
+//! 1. a matching deny rule: refuse
+//! 2. a matching ask rule: ask the user
+//! 3. a matching allow rule, or a grant the user gave this session: run
+//! 4. the default: read-only calls run, everything else asks
+//!
+//! Rules look like `bash`, `write_file(src/*)` or `bash(git status*)`: a
+//! tool name, optionally with a pattern matched against what the call acts
+//! on (the command for bash, the path for file tools). `*` matches anything.
+//! A bash command is split at `;`, `&&`, `|`, `$(...)` and so on, and every
+//! part must be allowed (deny and ask rules need only match one part), so
+//! `git status && rm -rf ~` doesn't sneak through `bash(git status*)`.
+//! Redirects to files are parts of their own (`> ~/.bashrc`), allowed by
+//! rules like `bash(> build/*)`.
+//!
+//! "Ask" means the prompt callback in the REPL. Without one (a single
+//! prompt from the command line) the non-interactive default applies.
+//! Calls running in parallel ask one at a time, and a call that waited for
+//! its turn is decided again, so an "always" just given covers it.
+
+use super::ToolExecutor;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Ask,
+    Deny,
+}
+
+/// The user's answer to a permission prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Yes,
+    No,
+    /// Yes, and don't ask again for calls like this one this session
+    Always,
+}
+
+/// Asks the user about a call: (description of the call, what "always" would allow)
+pub type Prompt = Arc<dyn Fn(&str, &str) -> Approval + Send + Sync>;
+
+/// `tool` or `tool(pattern)`
+#[derive(Debug, Clone)]
+pub struct Rule {
+    tool: String,
+    pattern: Option<String>,
+}
+
+impl Rule {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (tool, pattern) = match text.split_once('(') {
+            Some((tool, rest)) => {
+                let pattern = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("Rule '{}' is missing a closing parenthesis", text))?;
+                (tool.trim(), Some(pattern.trim().to_string()))
+            }
+            None => (text, None),
+        };
+        if tool.is_empty() {
+            return Err(format!("Rule '{}' has no tool name", text));
+        }
+        Ok(Self {
+            tool: tool.to_string(),
+            pattern,
+        })
+    }
+
+    /// Whether the rule covers one target of a call (None: the call has no
+    /// target, which only a rule without a pattern covers)
+    fn covers(&self, tool: &str, target: Option<&str>) -> bool {
+        wildcard_match(&self.tool, tool)
+            && match (&self.pattern, target) {
+                (None, _) => true,
+                (Some(pattern), Some(target)) => wildcard_match(pattern, target),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.pattern {
+            Some(pattern) => write!(f, "{}({})", self.tool, pattern),
+            None => write!(f, "{}", self.tool),
+        }
+    }
+}
+
+/// The permission layer in front of the registry (cheap to clone)
+#[derive(Clone)]
+pub struct Permissions {
+    rules: Vec<(Rule, Decision)>,
+    /// "Always" answers, remembered for the rest of the session
+    grants: Arc<Mutex<Vec<Rule>>>,
+    /// None when nobody is there to ask
+    prompt: Option<Prompt>,
+    /// Held while the prompt is showing, so parallel calls ask in turn
+    asking: Arc<Mutex<()>>,
+    /// What "ask" becomes without a prompt
+    unattended: Decision,
+}
+
+impl Permissions {
+    /// Rules only, no prompt: anything that would ask is refused
+    pub fn new(rules: Vec<(Rule, Decision)>) -> Self {
+        Self {
+            rules,
+            grants: Arc::new(Mutex::new(Vec::new())),
+            prompt: None,
+            asking: Arc::new(Mutex::new(())),
+            unattended: Decision::Deny,
+        }
+    }
+
+    /// Ask the user through `prompt` when a call needs approval
+    pub fn with_prompt(mut self, prompt: Prompt) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    /// Without a prompt, approve calls that would ask (deny rules still apply)
+    pub fn approve_unattended(mut self) -> Self {
+        self.unattended = Decision::Allow;
+        self
+    }
+
+    /// Ok if the call may run, Err explaining why not
+    pub fn check(&self, tool: &dyn ToolExecutor, input: &Value) -> Result<(), String> {
+        let name = tool.name();
+        let targets = tool.permission_targets(input);
+        let call = describe(name, &targets);
+
+        match self.decide(tool, input, &targets) {
+            Decision::Allow => Ok(()),
+            Decision::Deny => Err(format!("Permission denied: {} is blocked by a deny rule", call)),
+            Decision::Ask => {
+                let grants = grants_for(name, &targets);
+                let grant_text = grants.iter().map(Rule::to_string).collect::<Vec<_>>().join(" ");
+
+                let Some(prompt) = &self.prompt else {
+                    return match self.unattended {
+                        Decision::Allow => Ok(()),
+                        _ => Err(format!(
+                            "Permission denied: {} needs approval, and there is no one to ask. \
+                             The user can allow it with --allow '{}'",
+                            call, grant_text
+                        )),
+                    };
+                };
+
+                let _turn = self.asking.lock().unwrap_or_else(|e| e.into_inner());
+                if self.decide(tool, input, &targets) == Decision::Allow {
+                    return Ok(());
+                }
+                match prompt(&call, &grant_text) {
+                    Approval::Yes => Ok(()),
+                    Approval::Always => {
+                        self.grants.lock().unwrap_or_else(|e| e.into_inner()).extend(grants);
+                        Ok(())
+                    }
+                    Approval::No => Err(format!("The user declined: {}", call)),
+                }
+            }
+        }
+    }
+
+    fn decide(&self, tool: &dyn ToolExecutor, input: &Value, targets: &[String]) -> Decision {
+        let name = tool.name();
+        // A call without targets is checked as a single "no target" case
+        let targets: Vec<Option<&str>> = if targets.is_empty() {
+            vec![None]
+        } else {
+            targets.iter().map(|t| Some(t.as_str())).collect()
+        };
+        let any_rule = |decision: Decision, target: Option<&str>| {
+            self.rules
+                .iter()
+                .any(|(rule, d)| *d == decision && rule.covers(name, target))
+        };
+
+        if targets.iter().any(|&t| any_rule(Decision::Deny, t)) {
+            return Decision::Deny;
+        }
+        if targets.iter().any(|&t| any_rule(Decision::Ask, t)) {
+            return Decision::Ask;
+        }
+
+        let grants = self.grants.lock().unwrap_or_else(|e| e.into_inner());
+        let allowed = targets
+            .iter()
+            .all(|&t| any_rule(Decision::Allow, t) || grants.iter().any(|g| g.covers(name, t)));
+        if allowed || tool.is_read_only(input) {
+            Decision::Allow
+        } else {
+            Decision::Ask
+        }
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+/// "bash: cargo test" / "write_file: src/main.rs"
+fn describe(tool: &str, targets: &[String]) -> String {
+    if targets.is_empty() {
+        tool.to_string()
+    } else {
+        format!("{}: {}", tool, targets.join(" ; "))
+    }
+}
+
+/// What answering "always" allows: the first two words of each target
+/// (`bash(cargo test*)`), or the whole tool when the call has no target
+fn grants_for(tool: &str, targets: &[String]) -> Vec<Rule> {
+    if targets.is_empty() {
+        return vec![Rule {
+            tool: tool.to_string(),
+            pattern: None,
+        }];
+    }
+
+    let mut grants: Vec<Rule> = Vec::new();
+    for target in targets {
+        let prefix = target.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+        let pattern = format!("{}*", prefix);
+        if !grants.iter().any(|g| g.pattern.as_deref() == Some(pattern.as_str())) {
+            grants.push(Rule {
+                tool: tool.to_string(),
+                pattern: Some(pattern),
+            });
+        }
+    }
+    grants
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+
+    // Middle pieces must appear in order between the fixed ends
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
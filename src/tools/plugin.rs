@@ -0,0 +1,286 @@
+//! Plugin Tools - tools written in any language
+//!
+//! Topic 9: Designing a Tool System
+//!
+//! A plugin is an executable declared in a JSON config file:
+//!
+//! ```json
+//! {
+//!   "tools": [{
+//!     "name": "jira_issue",
+//!     "description": "Look up a Jira issue by key",
+//!     "input_schema": { "type": "object", "properties": { "key": { "type": "string" } } },
+//!     "command": ["python3", "plugins/jira.py"],
+//!     "timeout": 30
+//!   }]
+//! }
+//! ```
+//!
+//! Each call starts the command, writes the input JSON to its stdin and
+//! closes it, then reads one JSON object from stdout:
+//! `{"output": "...", "is_error": false}`. Anything else (bad JSON, a
+//! non-zero exit, running past the timeout) becomes an error carrying the
+//! end of the plugin's stderr. Relative paths in `command` are relative to
+//! the config file.
+//!
+//! Plugins go through the same schema check as built-in tools and run in
+//! the sandbox when there is one. The agent can't know what a plugin does,
+//! so every call needs the user's approval unless a rule allows it
+//! (`--allow jira_issue`), and stdout is capped like bash output.
+
+use super::bash::kill_group;
+use super::{CancelToken, Sandbox, ToolExecutor, ToolOutput};
+use crate::api::Tool;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Seconds a plugin gets when its config doesn't say
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// Extra time the registry allows beyond the plugin's own timeout
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// Lines of stderr included in an error
+const STDERR_LINES: usize = 20;
+
+/// Most a plugin may print on stdout
+const MAX_STDOUT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Bytes kept from the end of stderr
+const STDERR_TAIL_BYTES: usize = 8 * 1024;
+
+/// The config file: `{"tools": [...]}`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PluginConfig {
+    tools: Vec<PluginSpec>,
+}
+
+/// One tool in the config file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PluginSpec {
+    name: String,
+    description: String,
+    input_schema: Value,
+    /// Program and arguments
+    command: Vec<String>,
+    /// Seconds
+    timeout: Option<u64>,
+}
+
+/// What a plugin prints on stdout
+#[derive(Deserialize)]
+struct PluginResult {
+    output: String,
+    #[serde(default)]
+    is_error: bool,
+}
+
+pub struct PluginTool {
+    spec: PluginSpec,
+    sandbox: Option<Sandbox>,
+}
+
+impl PluginTool {
+    /// Read the plugins declared in a config file
+    pub fn load(path: &Path) -> Result<Vec<PluginTool>, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let config: PluginConfig =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid plugin config {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        config
+            .tools
+            .into_iter()
+            .map(|mut spec| {
+                validate(&spec).map_err(|e| format!("{}: plugin '{}': {}", path.display(), spec.name, e))?;
+                // "plugins/jira.py" is next to the config; "python3" is looked up on PATH
+                for arg in &mut spec.command {
+                    let beside = base.join(&*arg);
+                    if arg.contains('/') && Path::new(arg).is_relative() && beside.exists() {
+                        *arg = beside.to_string_lossy().into_owned();
+                    }
+                }
+                Ok(PluginTool { spec, sandbox: None })
+            })
+            .collect()
+    }
+
+    /// Run the plugin inside `sandbox`
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// The user's plugin config, if there is one:
+    /// $XDG_CONFIG_HOME/johnathan/plugins.json or ~/.config/johnathan/plugins.json
+    pub fn user_config() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        let path = base.join("johnathan").join("plugins.json");
+        path.is_file().then_some(path)
+    }
+
+    fn timeout_secs(&self) -> u64 {
+        self.spec.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS).max(1)
+    }
+}
+
+fn validate(spec: &PluginSpec) -> Result<(), String> {
+    let valid_name = !spec.name.is_empty()
+        && spec.name.len() <= 64
+        && spec.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_name {
+        return Err("name must be 1-64 letters, digits, '_' or '-'".to_string());
+    }
+    if spec.input_schema.get("type").and_then(Value::as_str) != Some("object") {
+        return Err("input_schema must be an object schema ({\"type\": \"object\", ...})".to_string());
+    }
+    if spec.command.first().is_none_or(|program| program.is_empty()) {
+        return Err("command must name a program".to_string());
+    }
+    Ok(())
+}
+
+impl ToolExecutor for PluginTool {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(&self.spec.name, &self.spec.description, self.spec.input_schema.clone())
+    }
+
+    fn timeout(&self, _input: &Value) -> Duration {
+        Duration::from_secs(self.timeout_secs()) + TIMEOUT_MARGIN
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        self.execute_cancellable(input, &CancelToken::new())
+    }
+
+    fn execute_cancellable(&self, input: Value, cancel: &CancelToken) -> Result<ToolOutput, String> {
+        let name = &self.spec.name;
+        let mut command = Command::new(&self.spec.command[0]);
+        command
+            .args(&self.spec.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(&mut command);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to start plugin {} ({}): {}", name, self.spec.command[0], e))?;
+
+        // Feed stdin and drain stdout/stderr on threads, so a plugin that
+        // writes before it has read everything can't deadlock us
+        let mut stdin = child.stdin.take().ok_or("Failed to open the plugin's stdin")?;
+        let request = input.to_string();
+        let writer = thread::spawn(move || {
+            let _ = stdin.write_all(request.as_bytes());
+        });
+        let stdout = child.stdout.take().ok_or("Failed to capture output")?;
+        let stderr = child.stderr.take().ok_or("Failed to capture output")?;
+        let stdout = thread::spawn(move || drain(stdout, MAX_STDOUT_BYTES, false));
+        let stderr = thread::spawn(move || drain(stderr, STDERR_TAIL_BYTES, true));
+
+        let timeout = Duration::from_secs(self.timeout_secs());
+        let start = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if start.elapsed() >= timeout || cancel.is_cancelled() => {
+                    kill_group(child.id());
+                    let _ = child.wait();
+                    break None;
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(format!("Failed to wait for plugin {}: {}", name, e)),
+            }
+        };
+        let _ = writer.join();
+        let (stdout, stdout_overflow) = stdout.join().unwrap_or_default();
+        let (stderr, _) = stderr.join().unwrap_or_default();
+
+        let failure = match status {
+            None if cancel.is_cancelled() => Some("was cancelled".to_string()),
+            None => Some(format!("timed out after {}s", timeout.as_secs())),
+            Some(status) if !status.success() => Some(match status.code() {
+                Some(code) => format!("exited with code {}", code),
+                None => "was killed by a signal".to_string(),
+            }),
+            Some(_) => None,
+        };
+        if let Some(failure) = failure {
+            return Err(format!("Plugin {} {}{}", name, failure, stderr_tail(&stderr)));
+        }
+        if stdout_overflow {
+            return Err(format!(
+                "Plugin {} printed more than {} MB; results must be smaller",
+                name,
+                MAX_STDOUT_BYTES >> 20
+            ));
+        }
+
+        let result: PluginResult = serde_json::from_str(stdout.trim()).map_err(|e| {
+            format!(
+                "Plugin {} printed no valid result ({}); expected {{\"output\": \"...\", \"is_error\": false}}{}",
+                name,
+                e,
+                stderr_tail(&stderr)
+            )
+        })?;
+        let output = ToolOutput::text(result.output);
+        Ok(if result.is_error {
+            ToolOutput { is_error: true, ..output }
+        } else {
+            output
+        })
+    }
+}
+
+/// Read `pipe` to the end, keeping at most `limit` bytes: the first ones,
+/// or with `tail` the last ones. Also says whether any were dropped.
+fn drain(mut pipe: impl Read, limit: usize, tail: bool) -> (String, bool) {
+    let mut bytes = Vec::new();
+    let mut dropped = false;
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = pipe.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        if tail {
+            bytes.extend_from_slice(&buf[..n]);
+            if bytes.len() > limit {
+                bytes.drain(..bytes.len() - limit);
+                dropped = true;
+            }
+        } else {
+            let room = limit - bytes.len();
+            bytes.extend_from_slice(&buf[..n.min(room)]);
+            dropped |= n > room;
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), dropped)
+}
+
+/// The last lines of stderr, for an error message ("" if there were none)
+fn stderr_tail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let tail = &lines[lines.len().saturating_sub(STDERR_LINES)..];
+    format!("\nstderr:\n{}", tail.join("\n"))
+}
@@ -0,0 +1,268 @@
+//! Read File Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Lets the model look at code. Output is numbered like `cat -n` so the
+//! model can refer to exact lines, and `offset`/`limit` let it page through
+//! large files instead of pulling them into the context all at once.
+//!
+//! Images (PNG, JPEG, GIF, WebP) come back as image blocks, so the model can
+//! look at screenshots and diagrams.
+
+use super::file_tracker::FileTracker;
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// Lines returned when no limit is given
+const DEFAULT_LIMIT: usize = 2000;
+
+/// Lines longer than this are cut off
+pub(super) const MAX_LINE_CHARS: usize = 2000;
+
+/// Total output cap, so one call can't flood the context window
+const MAX_OUTPUT_CHARS: usize = 100_000;
+
+/// Files larger than this are refused outright
+const MAX_FILE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// The API's size limit for a single image
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Extensions that are read as images rather than text
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// How much of the file is inspected to decide whether it's binary
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+pub struct ReadFileTool {
+    tracker: FileTracker,
+    workspace: Workspace,
+}
+
+impl ReadFileTool {
+    pub fn new(tracker: FileTracker, workspace: Workspace) -> Self {
+        Self { tracker, workspace }
+    }
+}
+
+impl ToolExecutor for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "read_file",
+            "Read a text file and return its contents with line numbers. \
+             Use offset and limit to read part of a large file. \
+             Image files (PNG, JPEG, GIF, WebP) are returned as images.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the file to read"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Line number to start from (1-based, default 1)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Maximum number of lines to return (default 2000)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        )
+    }
+
+    fn is_read_only(&self, _input: &Value) -> bool {
+        true
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        input["path"].as_str().map(str::to_string).into_iter().collect()
+    }
+
+    /// Already paged with offset/limit, and the way to read spilled output
+    fn spill_output(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let path = input["path"]
+            .as_str()
+            .ok_or("Missing required parameter: path")?;
+        let offset = input["offset"].as_u64().unwrap_or(1).max(1) as usize;
+        let limit = input["limit"].as_u64().unwrap_or(DEFAULT_LIMIT as u64) as usize;
+
+        let path = Path::new(path);
+        self.workspace.check(path)?;
+        let is_image = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+        if is_image {
+            let output = read_image(path)?;
+            self.tracker.record(path);
+            return Ok(output);
+        }
+
+        let text = read_text(path)?;
+        self.tracker.record(path);
+        let (numbered, truncated) = number_lines(&text, offset, limit);
+        Ok(ToolOutput::text(numbered).truncated(truncated))
+    }
+}
+
+/// Read an image file into an image block
+fn read_image(path: &Path) -> Result<ToolOutput, String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("Cannot access {}: {}", path.display(), e))?;
+    if metadata.len() > MAX_IMAGE_BYTES {
+        return Err(format!(
+            "{} is too large to view ({} bytes, limit {})",
+            path.display(),
+            metadata.len(),
+            MAX_IMAGE_BYTES
+        ));
+    }
+
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    // Trust the contents, not the extension: the API rejects a mislabeled image
+    let media_type = match bytes.as_slice() {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => return Err(format!("{} is not a PNG, JPEG, GIF or WebP image", path.display())),
+    };
+
+    Ok(ToolOutput::text(format!("{} ({}, {} bytes)", path.display(), media_type, bytes.len()))
+        .with_image(media_type, &bytes))
+}
+
+/// Read a file as text, with clear errors for everything that isn't one
+fn read_text(path: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("File not found: {}", path.display()),
+        std::io::ErrorKind::PermissionDenied => format!("Permission denied: {}", path.display()),
+        _ => format!("Cannot access {}: {}", path.display(), e),
+    })?;
+
+    if metadata.is_dir() {
+        return Err(format!("{} is a directory, not a file", path.display()));
+    }
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(format!(
+            "{} is too large to read ({} bytes, limit {})",
+            path.display(),
+            metadata.len(),
+            MAX_FILE_BYTES
+        ));
+    }
+
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    decode(&bytes).ok_or_else(|| {
+        format!(
+            "{} appears to be a binary file ({} bytes); not showing its contents",
+            path.display(),
+            bytes.len()
+        )
+    })
+}
+
+/// Decode file contents, detecting the encoding from BOMs and content
+///
+/// Returns None for binary data.
+fn decode(bytes: &[u8]) -> Option<String> {
+    // Byte order marks identify UTF-8 and UTF-16 unambiguously
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return Some(String::from_utf8_lossy(rest).into_owned());
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return Some(decode_utf16(rest, u16::from_le_bytes));
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return Some(decode_utf16(rest, u16::from_be_bytes));
+    }
+
+    // NUL bytes essentially never appear in text files
+    let sniff = &bytes[..bytes.len().min(BINARY_SNIFF_BYTES)];
+    if sniff.contains(&0) {
+        return None;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text.to_string()),
+        // Not UTF-8: treat as Latin-1, unless it's mostly control characters
+        Err(_) => {
+            let control = sniff
+                .iter()
+                .filter(|&&b| b < 0x20 && !matches!(b, b'\n' | b'\r' | b'\t' | 0x0C))
+                .count();
+            if control * 10 > sniff.len() {
+                None
+            } else {
+                Some(bytes.iter().map(|&b| b as char).collect())
+            }
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| to_u16([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Format lines `offset..offset+limit` as `cat -n` style output; true if anything was cut
+fn number_lines(text: &str, offset: usize, limit: usize) -> (String, bool) {
+    let total = text.lines().count();
+    if total == 0 {
+        return ("(empty file)".to_string(), false);
+    }
+    if offset > total {
+        return (
+            format!("(offset {} is past the end of the file, which has {} lines)", offset, total),
+            false,
+        );
+    }
+    let mut truncated = false;
+
+    let mut output = String::new();
+    let mut last_line = offset - 1;
+
+    for (i, line) in text.lines().enumerate().skip(offset - 1).take(limit) {
+        let line: String = if line.chars().count() > MAX_LINE_CHARS {
+            let cut: String = line.chars().take(MAX_LINE_CHARS).collect();
+            truncated = true;
+            format!("{}... [line truncated]", cut)
+        } else {
+            line.to_string()
+        };
+
+        let numbered = format!("{:>6}\t{}\n", i + 1, line);
+        if output.len() + numbered.len() > MAX_OUTPUT_CHARS {
+            break;
+        }
+        output.push_str(&numbered);
+        last_line = i + 1;
+    }
+
+    if last_line < total {
+        output.push_str(&format!(
+            "\n[showing lines {}-{} of {}; use offset={} to continue]",
+            offset,
+            last_line,
+            total,
+            last_line + 1
+        ));
+    }
+    (output, truncated || last_line < total)
+}
@@ -1,20 +1,52 @@
-/// Tool Registry - holds and manages available tools
-///
-/// When Claude requests a tool by name, we need to:
-/// 1. Find the right tool
-/// 2. Execute it
-/// 3. Return the result
-///
-/// The registry provides this lookup capability.
-
-use super::ToolExecutor;
+//! Tool Registry - holds and manages available tools
+//!
+//! When Claude requests a tool by name, we need to:
+//! 1. Find the right tool
+//! 2. Check the input against its schema
+//! 3. Check the call is permitted (see `permissions`)
+//! 4. Execute it
+//! 5. Return the result
+//!
+//! The registry provides this lookup capability.
+//!
+//! Each call runs on its own thread, so a tool can't take the session down
+//! with it: a panic becomes an error result, and a call that outlives its
+//! timeout is cancelled (see `cancel`) and reported as an error while the
+//! conversation carries on.
+//!
+//! Results over the output budget are cut to an excerpt, with the full
+//! text saved to a file (see `spill`).
+//!
+//! When the model makes several calls at once, consecutive read-only calls
+//! run in parallel (a few at a time); any other call waits for everything
+//! before it and runs alone. Results always come back in call order.
+
+use super::{schema, CancelToken, Permissions, Spill, ToolExecutor, ToolOutput};
 use crate::api::Tool;
+use std::any::Any;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a cancelled call gets to wind down before it is abandoned
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// Read-only calls run at the same time unless set otherwise
+const DEFAULT_MAX_PARALLEL: usize = 4;
 
 /// Holds all registered tools and provides lookup
 pub struct ToolRegistry {
     /// Tools indexed by name for O(1) lookup
-    tools: HashMap<String, Box<dyn ToolExecutor>>,
+    tools: HashMap<String, Arc<dyn ToolExecutor>>,
+    /// Rules and prompt deciding which calls may run
+    permissions: Permissions,
+    /// Read-only calls run at the same time, at most
+    max_parallel: usize,
+    /// Where oversized results go; None keeps them whole
+    spill: Option<Spill>,
 }
 
 impl ToolRegistry {
@@ -22,13 +54,32 @@ impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            permissions: Permissions::default(),
+            max_parallel: DEFAULT_MAX_PARALLEL,
+            spill: None,
         }
     }
 
+    /// Cut results over the output budget down, saving the full text to files
+    pub fn set_spill(&mut self, spill: Spill) {
+        self.spill = Some(spill);
+    }
+
+    /// How many read-only calls may run at the same time (1: one by one)
+    pub fn set_max_parallel(&mut self, max_parallel: usize) {
+        self.max_parallel = max_parallel.max(1);
+    }
+
+    /// Replace the permission policy (the default asks for, and without a
+    /// prompt refuses, every call that isn't read-only)
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
     /// Register a tool (takes ownership)
     pub fn register<T: ToolExecutor + 'static>(&mut self, tool: T) {
         let name = tool.name().to_string();
-        self.tools.insert(name, Box::new(tool));
+        self.tools.insert(name, Arc::new(tool));
     }
 
     /// Get tool definitions for sending to Claude
@@ -37,11 +88,154 @@ impl ToolRegistry {
     }
 
     /// Execute a tool by name with given input
-    pub fn execute(&self, name: &str, input: serde_json::Value) -> Result<String, String> {
-        match self.tools.get(name) {
-            Some(tool) => tool.execute(input),
-            None => Err(format!("Unknown tool: {}", name)),
+    ///
+    /// Input that doesn't match the tool's schema is rejected before the
+    /// tool runs, with an error describing each mismatch. Failures of any
+    /// kind come back as an is_error output, and the metadata's duration
+    /// and size are filled in here.
+    pub fn execute(&self, name: &str, input: serde_json::Value) -> ToolOutput {
+        let start = Instant::now();
+        let mut output = self.run(name, input).unwrap_or_else(ToolOutput::error);
+        if let Some(spill) = &self.spill
+            && self.tools.get(name).is_some_and(|t| t.spill_output())
+        {
+            output = spill.apply(name, output);
+        }
+        output.metadata.duration = Some(start.elapsed());
+        output.metadata.bytes = output.size();
+        output
+    }
+
+    /// Execute several calls, returning their outputs in the same order
+    ///
+    /// Runs of consecutive read-only calls go in parallel, up to the
+    /// parallel limit; every other call runs by itself, after the calls
+    /// before it have finished.
+    pub fn execute_all(&self, calls: Vec<(String, serde_json::Value)>) -> Vec<ToolOutput> {
+        let mut outputs = Vec::with_capacity(calls.len());
+        let mut batch = Vec::new();
+        for (name, input) in calls {
+            if self.is_read_only(&name, &input) {
+                batch.push((name, input));
+                continue;
+            }
+            outputs.extend(self.execute_parallel(std::mem::take(&mut batch)));
+            outputs.push(self.execute(&name, input));
+        }
+        outputs.extend(self.execute_parallel(batch));
+        outputs
+    }
+
+    /// Execute calls on up to `max_parallel` threads, outputs in call order
+    fn execute_parallel(&self, calls: Vec<(String, serde_json::Value)>) -> Vec<ToolOutput> {
+        if calls.len() <= 1 || self.max_parallel == 1 {
+            return calls.into_iter().map(|(name, input)| self.execute(&name, input)).collect();
+        }
+
+        let calls: Vec<Mutex<Option<(String, serde_json::Value)>>> =
+            calls.into_iter().map(|call| Mutex::new(Some(call))).collect();
+        let outputs: Vec<Mutex<Option<ToolOutput>>> = calls.iter().map(|_| Mutex::new(None)).collect();
+        let next = AtomicUsize::new(0);
+
+        // Each worker takes the next call not yet started until none are left
+        thread::scope(|scope| {
+            for _ in 0..self.max_parallel.min(calls.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(slot) = calls.get(i) else { break };
+                        let Some((name, input)) = slot.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+                            continue;
+                        };
+                        let output = self.execute(&name, input);
+                        *outputs[i].lock().unwrap_or_else(|e| e.into_inner()) = Some(output);
+                    }
+                });
+            }
+        });
+
+        outputs
+            .into_iter()
+            .map(|slot| {
+                slot.into_inner()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .unwrap_or_else(|| ToolOutput::error("The call did not run"))
+            })
+            .collect()
+    }
+
+    fn is_read_only(&self, name: &str, input: &serde_json::Value) -> bool {
+        self.tools.get(name).is_some_and(|t| t.is_read_only(input))
+    }
+
+    fn run(&self, name: &str, input: serde_json::Value) -> Result<ToolOutput, String> {
+        let tool = self
+            .tools
+            .get(name)
+            .ok_or_else(|| format!("Unknown tool: {}", name))?;
+
+        schema::validate(&tool.definition().input_schema, &input)
+            .map_err(|errors| format!("Invalid input for {}:\n{}", name, errors))?;
+        self.permissions.check(tool.as_ref(), &input)?;
+        isolated(Arc::clone(tool), input)
+    }
+
+    /// Whether a tool's output should be shown to the user as well
+    pub fn echoes_output(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|t| t.echo_output())
+    }
+}
+
+/// Run a call on a worker thread, turning panics and timeouts into errors
+fn isolated(tool: Arc<dyn ToolExecutor>, input: serde_json::Value) -> Result<ToolOutput, String> {
+    let name = tool.name().to_string();
+    let timeout = tool.timeout(&input);
+    let cancel = CancelToken::new();
+
+    let (sender, receiver) = mpsc::channel();
+    {
+        let cancel = cancel.clone();
+        thread::Builder::new()
+            .name(format!("tool-{}", name))
+            .spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| tool.execute_cancellable(input, &cancel)));
+                let _ = sender.send(result);
+            })
+            .map_err(|e| format!("Failed to start {}: {}", name, e))?;
+    }
+
+    let finished = match receiver.recv_timeout(timeout) {
+        Ok(finished) => finished,
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            return Err(format!("{} stopped without a result", name));
         }
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            cancel.cancel();
+            let note = format!("{} timed out after {}s", name, timeout.as_secs());
+            return match receiver.recv_timeout(CANCEL_GRACE) {
+                // Stopped when asked: keep whatever it had so far
+                Ok(Ok(Ok(output))) => {
+                    let mut output = output.with_text(format!("[{} and was cancelled]", note));
+                    output.is_error = true;
+                    Ok(output)
+                }
+                Ok(Ok(Err(e))) => Err(format!("{} and was cancelled: {}", note, e)),
+                _ => Err(format!("{}; it is still running in the background", note)),
+            };
+        }
+    };
+
+    finished.unwrap_or_else(|panic| Err(format!("{} crashed: {}", name, panic_message(&panic))))
+}
+
+/// The message a panic was raised with
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
     }
 }
 
@@ -0,0 +1,347 @@
+//! Sandbox - confining shell commands
+//!
+//! Topic 17: Safety and Sandboxing
+//!
+//! Permission rules decide whether a command runs; the sandbox limits what
+//! it can do once it runs. With `--sandbox`, every process a tool starts
+//! (bash commands, cargo with its build scripts and tests, git with its
+//! hooks, plugins) starts with:
+//! - Landlock: writes only beneath the workspace, the temp dir and any extra
+//!   directories (plus writing to devices like /dev/null); reads are not
+//!   restricted
+//! - seccomp: creating IPv4/IPv6 sockets (or an io_uring, which could
+//!   create them) fails, unless network is allowed. The filter fails closed:
+//!   32-bit and x32 system calls, which it can't inspect, kill the process
+//! - rlimits on CPU time, address space and file size (wall time is the
+//!   bash tool's own timeout)
+//!
+//! The restrictions are applied in the forked child just before `exec`, so
+//! the agent itself is unaffected. Only async-signal-safe calls are allowed
+//! there, so everything the child needs (the Landlock ruleset, the seccomp
+//! program) is built up front.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// CPU seconds a command may use (SIGXCPU after that)
+const CPU_LIMIT_SECS: u64 = 600;
+
+/// Address space per process
+const MEMORY_LIMIT_BYTES: u64 = 8 << 30;
+
+/// Largest file a command may write (SIGXFSZ after that)
+const FILE_SIZE_LIMIT_BYTES: u64 = 4 << 30;
+
+/// Output suggesting a command failed for lack of network
+const NETWORK_ERRORS: &[&str] = &[
+    "Could not resolve host",
+    "Temporary failure in name resolution",
+    "Name or service not known",
+    "Network is unreachable",
+];
+
+/// Output suggesting a command hit the address space limit
+const MEMORY_ERRORS: &[&str] = &["Cannot allocate memory", "memory allocation", "MemoryError", "out of memory"];
+
+// Landlock (linux/landlock.h)
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+/// ABI 2: linking or renaming a file into another directory
+const ACCESS_FS_REFER: u64 = 1 << 13;
+/// ABI 3: truncating a file
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+// seccomp_data field offsets and the architecture the filter is built for
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const SECCOMP_DATA_ARG0: u32 = 16;
+/// Set in x32 system call numbers
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// Cheap to clone: every tool that starts processes holds one
+#[derive(Clone)]
+pub struct Sandbox {
+    /// Landlock ruleset, applied to each child with landlock_restrict_self
+    ruleset: Arc<OwnedFd>,
+    /// Directories commands may write to
+    writable: Vec<PathBuf>,
+    /// seccomp program refusing IPv4/IPv6 sockets; None when network is allowed
+    filter: Option<Vec<libc::sock_filter>>,
+}
+
+impl Sandbox {
+    /// A sandbox allowing writes beneath `writable` and, if `network`, network access
+    pub fn new(writable: Vec<PathBuf>, network: bool) -> Result<Self, String> {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Err(format!(
+                "The sandbox needs Landlock (Linux 5.13+, enabled with lsm=...,landlock), \
+                 which is not available: {}",
+                io::Error::last_os_error()
+            ));
+        }
+
+        let mut write_access = ACCESS_FS_WRITE_FILE
+            | ACCESS_FS_REMOVE_DIR
+            | ACCESS_FS_REMOVE_FILE
+            | ACCESS_FS_MAKE_CHAR
+            | ACCESS_FS_MAKE_DIR
+            | ACCESS_FS_MAKE_REG
+            | ACCESS_FS_MAKE_SOCK
+            | ACCESS_FS_MAKE_FIFO
+            | ACCESS_FS_MAKE_BLOCK
+            | ACCESS_FS_MAKE_SYM;
+        if abi >= 2 {
+            write_access |= ACCESS_FS_REFER;
+        }
+        let file_access = if abi >= 3 {
+            write_access |= ACCESS_FS_TRUNCATE;
+            ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE
+        } else {
+            ACCESS_FS_WRITE_FILE
+        };
+
+        let attr = RulesetAttr {
+            handled_access_fs: write_access,
+        };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(format!(
+                "Could not create the Landlock ruleset: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        let mut dirs = Vec::new();
+        for dir in writable {
+            let dir = dir
+                .canonicalize()
+                .map_err(|e| format!("Sandbox directory {}: {}", dir.display(), e))?;
+            add_rule(&ruleset, &dir, write_access)?;
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        // Writing to /dev/null, /dev/tty and friends, but not creating files there
+        add_rule(&ruleset, Path::new("/dev"), file_access)?;
+
+        let filter = if network { None } else { Some(socket_filter()?) };
+
+        Ok(Self {
+            ruleset: Arc::new(ruleset),
+            writable: dirs,
+            filter,
+        })
+    }
+
+    /// Confine `command` when it is spawned
+    pub fn apply(&self, command: &mut Command) {
+        let ruleset = self.ruleset.as_raw_fd();
+        let filter = self.filter.clone();
+        unsafe {
+            command.pre_exec(move || confine(ruleset, filter.as_deref()));
+        }
+    }
+
+    /// "writes limited to /work, /tmp; network blocked"
+    pub fn describe(&self) -> String {
+        let dirs: Vec<String> = self.writable.iter().map(|d| d.display().to_string()).collect();
+        format!(
+            "writes limited to {}; network {}",
+            dirs.join(", "),
+            if self.filter.is_some() { "blocked" } else { "allowed" }
+        )
+    }
+
+    /// A note explaining how the sandbox may have caused a failed command,
+    /// given its exit code and output
+    pub fn explain(&self, code: Option<i32>, output: &str) -> Option<String> {
+        let reason = match code {
+            Some(0) => return None,
+            Some(c) if c == 128 + libc::SIGXCPU => {
+                format!("the command used up its {}s of CPU time", CPU_LIMIT_SECS)
+            }
+            Some(c) if c == 128 + libc::SIGSYS => {
+                "the command made a system call the sandbox refuses (32-bit and x32 programs can't run in it)"
+                    .to_string()
+            }
+            Some(c) if c == 128 + libc::SIGXFSZ => format!(
+                "the command tried to write a file over {} GB",
+                FILE_SIZE_LIMIT_BYTES >> 30
+            ),
+            _ if output.contains("Permission denied") || output.contains("Operation not permitted") => {
+                format!("\"Permission denied\" may mean it was blocked ({})", self.describe())
+            }
+            _ if self.filter.is_some() && NETWORK_ERRORS.iter().any(|e| output.contains(e)) => {
+                "network access is blocked".to_string()
+            }
+            _ if MEMORY_ERRORS.iter().any(|e| output.contains(e)) => format!(
+                "processes are limited to {} GB of address space",
+                MEMORY_LIMIT_BYTES >> 30
+            ),
+            _ => return None,
+        };
+        Some(format!("[sandbox: {}]", reason))
+    }
+}
+
+/// Allow `access` beneath `path` in the ruleset
+fn add_rule(ruleset: &OwnedFd, path: &Path, access: u64) -> Result<(), String> {
+    let dir = File::options()
+        .read(true)
+        .custom_flags(libc::O_PATH)
+        .open(path)
+        .map_err(|e| format!("Sandbox directory {}: {}", path.display(), e))?;
+    let attr = PathBeneathAttr {
+        allowed_access: access,
+        parent_fd: dir.as_raw_fd(),
+    };
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            LANDLOCK_RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0,
+        )
+    };
+    if result < 0 {
+        return Err(format!(
+            "Could not add {} to the sandbox: {}",
+            path.display(),
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// A seccomp program making socket(AF_INET/AF_INET6, ...) and io_uring_setup
+/// fail with EACCES, and killing the process on a system call from another
+/// architecture or ABI
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn socket_filter() -> Result<Vec<libc::sock_filter>, String> {
+    let load = |offset| bpf(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0, 0, offset);
+    let jump_if = |value, yes, no| bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, yes, no, value);
+    let jump_if_at_least = |value, yes, no| bpf(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, yes, no, value);
+    let ret = |value| bpf(libc::BPF_RET | libc::BPF_K, 0, 0, value);
+
+    // Jump offsets count instructions after the jump
+    Ok(vec![
+        load(SECCOMP_DATA_ARCH), // 0
+        jump_if(AUDIT_ARCH, 0, 9), // 1
+        load(SECCOMP_DATA_NR), // 2
+        jump_if_at_least(X32_SYSCALL_BIT, 7, 0), // 3
+        jump_if(libc::SYS_io_uring_setup as u32, 5, 0), // 4
+        jump_if(libc::SYS_socket as u32, 0, 3), // 5
+        load(SECCOMP_DATA_ARG0), // 6
+        jump_if(libc::AF_INET as u32, 2, 0), // 7
+        jump_if(libc::AF_INET6 as u32, 1, 0), // 8
+        ret(libc::SECCOMP_RET_ALLOW), // 9
+        ret(libc::SECCOMP_RET_ERRNO | libc::EACCES as u32), // 10
+        ret(libc::SECCOMP_RET_KILL_PROCESS), // 11
+    ])
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn socket_filter() -> Result<Vec<libc::sock_filter>, String> {
+    Err("Blocking network access is not supported on this architecture; allow it with --sandbox-network".to_string())
+}
+
+fn bpf(code: u32, jt: u8, jf: u8, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Runs in the child between fork and exec: no allocation, only syscalls
+fn confine(ruleset: RawFd, filter: Option<&[libc::sock_filter]>) -> io::Result<()> {
+    // (resource, soft, hard): past the soft CPU limit comes SIGXCPU, which
+    // the command can report, and SIGKILL only a little later
+    for (resource, soft, hard) in [
+        (libc::RLIMIT_CPU, CPU_LIMIT_SECS, CPU_LIMIT_SECS + 5),
+        (libc::RLIMIT_AS, MEMORY_LIMIT_BYTES, MEMORY_LIMIT_BYTES),
+        (libc::RLIMIT_FSIZE, FILE_SIZE_LIMIT_BYTES, FILE_SIZE_LIMIT_BYTES),
+    ] {
+        let limit = libc::rlimit {
+            rlim_cur: soft,
+            rlim_max: hard,
+        };
+        check(unsafe { libc::setrlimit(resource, &limit) } as libc::c_long)?;
+    }
+
+    // Required for both Landlock and seccomp without privileges
+    check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } as libc::c_long)?;
+    check(unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0) })?;
+
+    if let Some(filter) = filter {
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_ptr() as *mut libc::sock_filter,
+        };
+        check(unsafe {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const libc::sock_fprog,
+            )
+        } as libc::c_long)?;
+    }
+    Ok(())
+}
+
+fn check(result: libc::c_long) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
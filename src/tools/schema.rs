@@ -0,0 +1,267 @@
+//! Input Validation against a Tool's JSON Schema
+//!
+//! Topic 15: Error Handling and Recovery
+//!
+//! Every tool already describes its input with a JSON Schema, so the
+//! registry checks input against it before the tool runs. A mismatch goes
+//! back to the model as an error listing every problem ("timeout: expected
+//! integer, got string"), which is usually enough for it to fix the call on
+//! the next try.
+//!
+//! This is the subset of JSON Schema our tools use (including what
+//! schemars generates for typed tools): type, required, properties,
+//! additionalProperties, items, enum, const, anyOf/oneOf, and numeric,
+//! length and size bounds. Unknown keywords are ignored rather than rejected.
+
+use serde_json::Value;
+
+/// Check `input` against `schema`; Err lists every problem found
+pub fn validate(schema: &Value, input: &Value) -> Result<(), String> {
+    let mut errors = Vec::new();
+    check(schema, input, "input", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                path,
+                types.join(" or "),
+                describe(value)
+            ));
+            // Further checks would only repeat the same mistake
+            return;
+        }
+    }
+
+    // anyOf/oneOf: at least one alternative must accept the value (oneOf's
+    // "exactly one" rule doesn't matter for catching mistakes)
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(alternatives) = schema.get(keyword).and_then(Value::as_array) {
+            let mut attempts = alternatives.iter().map(|alternative| {
+                let mut alternative_errors = Vec::new();
+                check(alternative, value, path, &mut alternative_errors);
+                alternative_errors
+            });
+            if let Some(first) = attempts.next()
+                && !first.is_empty()
+                && attempts.all(|errors| !errors.is_empty())
+            {
+                errors.push(format!("{}: does not match any of the allowed forms", path));
+                errors.extend(first);
+            }
+        }
+    }
+
+    if let Some(expected) = schema.get("const")
+        && value != expected
+    {
+        errors.push(format!("{}: expected {}, got {}", path, expected, value));
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        let options: Vec<String> = allowed.iter().map(Value::to_string).collect();
+        errors.push(format!(
+            "{}: {} is not one of {}",
+            path,
+            value,
+            options.join(", ")
+        ));
+    }
+
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum")
+                && n < min
+            {
+                errors.push(format!("{}: {} is less than the minimum {}", path, n, min));
+            }
+            if let Some(max) = bound("maximum")
+                && n > max
+            {
+                errors.push(format!("{}: {} is greater than the maximum {}", path, n, max));
+            }
+            if let Some(min) = bound("exclusiveMinimum")
+                && n <= min
+            {
+                errors.push(format!("{}: {} must be greater than {}", path, n, min));
+            }
+            if let Some(max) = bound("exclusiveMaximum")
+                && n >= max
+            {
+                errors.push(format!("{}: {} must be less than {}", path, n, max));
+            }
+        }
+
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                && len < min
+            {
+                errors.push(format!("{}: must be at least {} characters", path, min));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && len > max
+            {
+                errors.push(format!("{}: must be at most {} characters", path, max));
+            }
+        }
+
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && len < min
+            {
+                errors.push(format!("{}: must have at least {} items", path, min));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                && len > max
+            {
+                errors.push(format!("{}: must have at most {} items", path, max));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+
+        Value::Object(fields) => {
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        errors.push(format!("{}: missing required property '{}'", path, name));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                let field_path = if path == "input" {
+                    name.clone()
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => check(field_schema, field, &field_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{}: unknown property", field_path))
+                        }
+                        Some(extra @ Value::Object(_)) => check(extra, field, &field_path, errors),
+                        _ => {}
+                    },
+                }
+            }
+        }
+
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        // Stricter than JSON Schema (which allows 3.0): tools read integers with as_u64/as_i64
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+/// The value's JSON type, with the value itself when it is short
+fn describe(value: &Value) -> String {
+    let kind = match value {
+        Value::Null => return "null".to_string(),
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => return "array".to_string(),
+        Value::Object(_) => return "object".to_string(),
+    };
+    let text = value.to_string();
+    if text.len() <= 40 {
+        format!("{} {}", kind, text)
+    } else {
+        kind.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "minLength": 1 },
+                "timeout": { "type": "integer", "minimum": 1, "maximum": 600 },
+                "mode": { "enum": ["fast", "slow"] },
+                "tags": { "type": "array", "items": { "type": "string" }, "maxItems": 2 },
+                "limit": { "anyOf": [{ "type": "integer" }, { "type": "null" }] }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn accepts_valid_input() {
+        let input = json!({ "path": "a.rs", "timeout": 30, "mode": "fast", "tags": ["x"], "limit": null });
+        assert_eq!(validate(&schema(), &input), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let input = json!({ "timeout": "30", "mode": "medium", "extra": 1 });
+        let errors = validate(&schema(), &input).unwrap_err();
+        assert!(errors.contains("input: missing required property 'path'"), "{}", errors);
+        assert!(errors.contains("timeout: expected integer, got string \"30\""), "{}", errors);
+        assert!(errors.contains("mode: \"medium\" is not one of \"fast\", \"slow\""), "{}", errors);
+        assert!(errors.contains("extra: unknown property"), "{}", errors);
+        assert_eq!(errors.lines().count(), 4);
+    }
+
+    #[test]
+    fn checks_bounds_and_nested_items() {
+        let input = json!({ "path": "", "timeout": 0, "tags": ["a", 2, "c"] });
+        let errors = validate(&schema(), &input).unwrap_err();
+        assert!(errors.contains("path: must be at least 1 characters"), "{}", errors);
+        assert!(errors.contains("timeout: 0 is less than the minimum 1"), "{}", errors);
+        assert!(errors.contains("tags: must have at most 2 items"), "{}", errors);
+        assert!(errors.contains("tags[1]: expected string, got number 2"), "{}", errors);
+    }
+
+    #[test]
+    fn integer_rejects_fractions() {
+        let errors = validate(&schema(), &json!({ "path": "a", "timeout": 3.5 })).unwrap_err();
+        assert!(errors.contains("timeout: expected integer"), "{}", errors);
+    }
+
+    #[test]
+    fn any_of_needs_one_matching_alternative() {
+        let errors = validate(&schema(), &json!({ "path": "a", "limit": "ten" })).unwrap_err();
+        assert!(errors.contains("limit: does not match any of the allowed forms"), "{}", errors);
+    }
+}
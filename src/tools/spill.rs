@@ -0,0 +1,162 @@
+//! Spill - oversized output goes to a file, the model gets an excerpt
+//!
+//! Topic 14: Context Window Strategies
+//!
+//! One `cat` of a log file can fill the context window. Every tool result
+//! passes through here: if its text is over the budget, the full text is
+//! written to a scratch file for the session and the model receives the
+//! first and last lines, the file's path and size, and a pointer to
+//! read_file for paging through the rest. Lines too long for read_file are
+//! wrapped in the saved file, so every part of it can be paged to. Images
+//! are left alone.
+
+use super::read_file::MAX_LINE_CHARS;
+use super::ToolOutput;
+use crate::api::ContentBlock;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+/// Characters of the excerpt taken from each end
+const EXCERPT_CHARS: usize = 8_000;
+
+pub struct Spill {
+    /// Private scratch directory for this session, removed when the session ends
+    dir: TempDir,
+    budget: usize,
+    /// Numbers the files in the order they were written
+    count: AtomicUsize,
+}
+
+impl Spill {
+    /// Spill results over `budget` characters into a new scratch directory
+    pub fn new(budget: usize) -> Result<Self, String> {
+        let dir = tempfile::Builder::new()
+            .prefix("johnathan-output-")
+            .permissions(fs::Permissions::from_mode(0o700))
+            .tempdir()
+            .map_err(|e| format!("Cannot create a directory for large tool output: {}", e))?;
+        Ok(Self {
+            dir,
+            budget,
+            count: AtomicUsize::new(0),
+        })
+    }
+
+    /// Where the full outputs are written
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// `output` as is if it fits the budget, otherwise an excerpt pointing
+    /// at the full text on disk
+    pub fn apply(&self, tool: &str, output: ToolOutput) -> ToolOutput {
+        let text: Vec<&str> = output
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        let text = text.join("\n");
+        if text.chars().count() <= self.budget {
+            return output;
+        }
+
+        let n = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        let path = self.dir().join(format!("{:03}-{}.txt", n, tool));
+        let (text, wrapped) = wrap_long_lines(&text);
+        let note = match fs::write(&path, &text) {
+            Ok(()) => format!(
+                "[output too large for the context ({} bytes, {} lines{}); the full output is in {}. \
+                 Page through it with read_file using offset and limit, or search it with grep]",
+                text.len(),
+                text.lines().count(),
+                if wrapped {
+                    format!(", lines over {} characters wrapped", MAX_LINE_CHARS)
+                } else {
+                    String::new()
+                },
+                path.display()
+            ),
+            Err(e) => format!(
+                "[output too large for the context ({} bytes); saving it to {} failed: {}]",
+                text.len(),
+                path.display(),
+                e
+            ),
+        };
+
+        let excerpt = ContentBlock::Text {
+            text: format!("{}\n\n{}", note, excerpt(&text)),
+        };
+        let mut blocks = vec![excerpt];
+        blocks.extend(
+            output
+                .blocks
+                .into_iter()
+                .filter(|block| !matches!(block, ContentBlock::Text { .. })),
+        );
+        ToolOutput { blocks, ..output }.truncated(true)
+    }
+}
+
+/// `text` with lines longer than read_file shows split into pieces, and
+/// whether any were
+fn wrap_long_lines(text: &str) -> (String, bool) {
+    if text.lines().all(|line| line.chars().count() <= MAX_LINE_CHARS) {
+        return (text.to_string(), false);
+    }
+    let mut wrapped = String::with_capacity(text.len());
+    for line in text.lines() {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            wrapped.push('\n');
+        }
+        for piece in chars.chunks(MAX_LINE_CHARS) {
+            wrapped.extend(piece);
+            wrapped.push('\n');
+        }
+    }
+    (wrapped, true)
+}
+
+/// Whole lines from the start and end of `text`, up to EXCERPT_CHARS each,
+/// labelled with their line numbers
+fn excerpt(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut head_end = 0;
+    let mut chars = 0;
+    while head_end < lines.len() && chars + lines[head_end].len() < EXCERPT_CHARS {
+        chars += lines[head_end].len() + 1;
+        head_end += 1;
+    }
+
+    let mut tail_start = lines.len();
+    chars = 0;
+    while tail_start > head_end && chars + lines[tail_start - 1].len() < EXCERPT_CHARS {
+        chars += lines[tail_start - 1].len() + 1;
+        tail_start -= 1;
+    }
+
+    // A huge first line: show its start, cut on a character boundary
+    if head_end == 0 {
+        let start: String = text.chars().take(EXCERPT_CHARS).collect();
+        return format!("--- first {} characters ---\n{}", EXCERPT_CHARS, start);
+    }
+
+    let mut excerpt = format!("--- lines 1-{} ---\n{}", head_end, lines[..head_end].join("\n"));
+    if tail_start < lines.len() {
+        excerpt.push_str(&format!(
+            "\n\n--- lines {}-{} ---\n{}",
+            tail_start + 1,
+            lines.len(),
+            lines[tail_start..].join("\n")
+        ));
+    }
+    excerpt
+}
@@ -0,0 +1,127 @@
+//! Todo Tool
+//!
+//! Topic 19: Multi-turn Planning
+//!
+//! Long tasks take many tool rounds, and by round fifteen the model has
+//! lost track of the plan it made in round one. The todo list gives it a
+//! place to write the plan down: each call replaces the whole list (so the
+//! model never has to address items by index), and the rendered checklist
+//! comes back as the result and is shown to the user.
+//!
+//! The list lives in the session, not in the conversation, so it is saved
+//! and restored with the session (see `crate::session`).
+
+use super::{ToolOutput, TypedTool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TodoItem {
+    /// What needs to be done
+    pub content: String,
+    pub status: TodoStatus,
+}
+
+/// The session's task list, shared by the tool and the REPL (cheap to clone)
+#[derive(Clone, Default)]
+pub struct TodoList {
+    items: Arc<Mutex<Vec<TodoItem>>>,
+}
+
+impl TodoList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn items(&self) -> Vec<TodoItem> {
+        self.items.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, items: Vec<TodoItem>) {
+        *self.items.lock().unwrap_or_else(|e| e.into_inner()) = items;
+    }
+
+    /// The list as a terminal checklist
+    pub fn render(&self) -> String {
+        let items = self.items();
+        if items.is_empty() {
+            return "Todo list is empty".to_string();
+        }
+
+        let done = items.iter().filter(|i| i.status == TodoStatus::Done).count();
+        let mut output = format!("Todo ({}/{} done):", done, items.len());
+        for item in &items {
+            let mark = match item.status {
+                TodoStatus::Pending => "[ ]",
+                TodoStatus::InProgress => "[~]",
+                TodoStatus::Done => "[x]",
+            };
+            output.push_str(&format!("\n  {} {}", mark, item.content));
+        }
+        output
+    }
+}
+
+pub struct TodoTool {
+    list: TodoList,
+}
+
+impl TodoTool {
+    pub fn new(list: TodoList) -> Self {
+        Self { list }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TodoInput {
+    /// The full task list, in order
+    todos: Vec<TodoItem>,
+}
+
+impl TypedTool for TodoTool {
+    type Input = TodoInput;
+
+    fn name(&self) -> &str {
+        "todo_write"
+    }
+
+    fn description(&self) -> &str {
+        "Create or update the task list for multi-step work. Send the complete list every \
+         time; it replaces the previous one. Mark a task in_progress before starting it \
+         (only one at a time) and done as soon as it is finished. Skip this for simple, \
+         single-step requests."
+    }
+
+    fn run(&self, input: TodoInput) -> Result<ToolOutput, String> {
+        let items = input.todos;
+        if let Some(empty) = items.iter().position(|i| i.content.trim().is_empty()) {
+            return Err(format!("Task {} has no content", empty + 1));
+        }
+        let in_progress = items
+            .iter()
+            .filter(|i| i.status == TodoStatus::InProgress)
+            .count();
+        if in_progress > 1 {
+            return Err(format!(
+                "{} tasks are in_progress; work on one at a time",
+                in_progress
+            ));
+        }
+
+        self.list.replace(items);
+        Ok(ToolOutput::text(self.list.render()))
+    }
+
+    fn echo_output(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,96 @@
+//! Typed Tools - input as a struct, schema generated from it
+//!
+//! Topic 9: Designing a Tool System
+//!
+//! A `ToolExecutor` hand-writes its schema in `definition()` and then picks
+//! fields out of a `serde_json::Value` in `execute()`; nothing keeps the two
+//! in sync. A `TypedTool` instead declares an input struct deriving
+//! `Deserialize` and `JsonSchema`:
+//! - the schema sent to Claude is generated from the struct (field doc
+//!   comments become descriptions, `Option` fields are optional)
+//! - the input is deserialized into the struct before `run` sees it
+//!
+//! A blanket impl turns every `TypedTool` into a `ToolExecutor`, so typed
+//! tools register like any other.
+
+use super::{ToolExecutor, ToolOutput};
+use crate::api::Tool;
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub trait TypedTool: Send + Sync {
+    /// The tool's input, e.g. `#[derive(Deserialize, JsonSchema)] struct Input {..}`
+    type Input: DeserializeOwned + JsonSchema;
+
+    /// Unique name of the tool (must match what's sent to Claude)
+    fn name(&self) -> &str;
+
+    /// What the tool does and when to use it
+    fn description(&self) -> &str;
+
+    /// Execute the tool with already-parsed input
+    fn run(&self, input: Self::Input) -> Result<ToolOutput, String>;
+
+    /// Whether the user should see the output too
+    fn echo_output(&self) -> bool {
+        false
+    }
+
+    /// Whether calls only look at things (see `ToolExecutor::is_read_only`)
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// What a call acts on (see `ToolExecutor::permission_targets`)
+    fn permission_targets(&self, _input: &Self::Input) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl<T: TypedTool> ToolExecutor for T {
+    fn name(&self) -> &str {
+        TypedTool::name(self)
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(TypedTool::name(self), self.description(), input_schema::<T::Input>())
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let input = serde_json::from_value(input).map_err(|e| format!("Invalid input: {}", e))?;
+        self.run(input)
+    }
+
+    fn echo_output(&self) -> bool {
+        TypedTool::echo_output(self)
+    }
+
+    fn is_read_only(&self, _input: &Value) -> bool {
+        TypedTool::is_read_only(self)
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        serde_json::from_value(input.clone())
+            .map(|input| TypedTool::permission_targets(self, &input))
+            .unwrap_or_default()
+    }
+}
+
+/// Generate the `input_schema` for a type
+///
+/// Nested types are inlined rather than referenced through `$defs`, and
+/// the `$schema`/`title` annotations are dropped: the API and our validator
+/// only need the plain schema.
+pub fn input_schema<I: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft2020_12()
+        .with(|s| s.inline_subschemas = true)
+        .into_generator();
+    let mut schema = generator.into_root_schema_for::<I>().to_value();
+    if let Some(fields) = schema.as_object_mut() {
+        fields.remove("$schema");
+        fields.remove("title");
+    }
+    schema
+}
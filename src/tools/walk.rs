@@ -0,0 +1,91 @@
+//! Workspace walking shared by the search tools
+//!
+//! Built on the `ignore` crate (the walker behind ripgrep), so `.gitignore`,
+//! `.ignore` and git's global excludes are honored and build output like
+//! `target/` never shows up in results.
+
+use ignore::overrides::OverrideBuilder;
+use ignore::types::TypesBuilder;
+use ignore::WalkBuilder;
+use super::Workspace;
+use std::path::Path;
+
+/// Files larger than this are not searched
+pub const MAX_SEARCH_BYTES: u64 = 5 * 1024 * 1024;
+
+/// How much of a file is inspected to decide whether it's binary
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// Optional filters narrowing which files are walked
+#[derive(Default)]
+pub struct WalkFilter<'a> {
+    /// Glob the file must match, e.g. "*.rs" or "src/**/*.toml"
+    pub glob: Option<&'a str>,
+    /// ripgrep file type name, e.g. "rust", "py", "js"
+    pub file_type: Option<&'a str>,
+    /// Globs to leave out, in addition to the ignore files
+    pub exclude: &'a [String],
+    /// Leave out what the workspace doesn't permit (secrets, links leading out)
+    pub workspace: Option<&'a Workspace>,
+}
+
+/// Walk `root` (sorted by name), respecting ignore files
+pub fn walker(root: &Path, filter: &WalkFilter) -> Result<WalkBuilder, String> {
+    if !root.exists() {
+        return Err(format!("Path not found: {}", root.display()));
+    }
+
+    let workspace = filter.workspace.cloned();
+    let mut builder = WalkBuilder::new(root);
+    builder
+        // Dotfiles like .github/ are often relevant; .git/ itself never is
+        .hidden(false)
+        .filter_entry(move |entry| {
+            entry.file_name() != ".git" && workspace.as_ref().is_none_or(|w| w.permits(entry.path()))
+        })
+        .sort_by_file_name(|a, b| a.cmp(b));
+
+    // Overrides: a plain glob whitelists, a "!glob" excludes
+    if filter.glob.is_some() || !filter.exclude.is_empty() {
+        let mut overrides = OverrideBuilder::new(root);
+        if let Some(glob) = filter.glob {
+            overrides
+                .add(glob)
+                .map_err(|e| format!("Invalid glob '{}': {}", glob, e))?;
+        }
+        for glob in filter.exclude {
+            overrides
+                .add(&format!("!{}", glob))
+                .map_err(|e| format!("Invalid ignore glob '{}': {}", glob, e))?;
+        }
+        let overrides = overrides
+            .build()
+            .map_err(|e| format!("Invalid globs: {}", e))?;
+        builder.overrides(overrides);
+    }
+
+    if let Some(file_type) = filter.file_type {
+        let types = TypesBuilder::new()
+            .add_defaults()
+            .select(file_type)
+            .build()
+            .map_err(|e| format!("Invalid file type '{}': {}", file_type, e))?;
+        builder.types(types);
+    }
+
+    Ok(builder)
+}
+
+/// NUL bytes near the start mean the file isn't text
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0)
+}
+
+/// Path as shown to the model: without a leading "./"
+pub fn display_path(path: &Path) -> String {
+    match path.strip_prefix(".") {
+        Ok(rest) if rest.as_os_str().is_empty() => ".".to_string(),
+        Ok(rest) => rest.display().to_string(),
+        Err(_) => path.display().to_string(),
+    }
+}
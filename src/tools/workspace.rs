@@ -0,0 +1,185 @@
+//! Workspace - the directories file tools may touch
+//!
+//! Topic 17: Safety and Sandboxing
+//!
+//! Every tool that takes a path checks it here first. A path is allowed if,
+//! after resolving symlinks, it lies inside the workspace root (where the
+//! agent was started) or a directory the user added, and it isn't a
+//! secret. That means:
+//! - `..` is refused outright, even when it would stay inside
+//! - a symlink inside the workspace pointing elsewhere is refused
+//! - paths that don't exist yet (a file about to be written) are checked
+//!   through their nearest existing parent
+//! - `.env` files, private keys and directories like `~/.ssh` are refused
+//!   unless the user exposed them explicitly
+//!
+//! The search tools use `permits` to leave such files out of their walks.
+
+use std::path::{Component, Path, PathBuf};
+
+/// File names that usually hold secrets (`*` matches anything)
+const SECRET_FILES: &[&str] = &[
+    ".env",
+    ".env.*",
+    "*.pem",
+    "*.key",
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    ".netrc",
+    ".pgpass",
+];
+
+/// Templates that look like secrets but are meant to be committed
+const NOT_SECRET_FILES: &[&str] = &[".env.example", ".env.sample", ".env.template"];
+
+/// Directories that hold credentials, wherever they are
+const SECRET_DIRS: &[&str] = &[".ssh", ".gnupg", ".aws", ".kube", ".docker"];
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Canonical root; relative paths are relative to it
+    root: PathBuf,
+    /// Other canonical directories the user allowed
+    extra: Vec<PathBuf>,
+    /// Canonical secret files or directories the user allowed anyway
+    exposed: Vec<PathBuf>,
+}
+
+impl Workspace {
+    pub fn new(root: &Path) -> Result<Self, String> {
+        Ok(Self {
+            root: canonical(root)?,
+            extra: Vec::new(),
+            exposed: Vec::new(),
+        })
+    }
+
+    /// Also allow paths beneath `dir`
+    pub fn add_dir(mut self, dir: &Path) -> Result<Self, String> {
+        self.extra.push(canonical(dir)?);
+        Ok(self)
+    }
+
+    /// Allow a secret file, or the secrets beneath a directory
+    pub fn expose(mut self, path: &Path) -> Result<Self, String> {
+        self.exposed.push(canonical(path)?);
+        Ok(self)
+    }
+
+    /// Ok if a tool may touch `path`, Err saying why not
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        if path.components().any(|c| c == Component::ParentDir) {
+            return Err(format!(
+                "{} contains '..'; use a path inside the workspace ({})",
+                path.display(),
+                self.root.display()
+            ));
+        }
+
+        let absolute = self.root.join(path);
+        let resolved = resolve(&absolute)?;
+        let Some(dir) = self.containing(&resolved) else {
+            return Err(if self.containing(&absolute).is_some() {
+                format!(
+                    "{} is a symlink leading outside the workspace (to {})",
+                    path.display(),
+                    resolved.display()
+                )
+            } else {
+                format!(
+                    "{} is outside the workspace ({}); the user can allow another directory with --add-dir",
+                    path.display(),
+                    self.root.display()
+                )
+            });
+        };
+
+        // Only what lies below the allowed directory counts, so a workspace
+        // that itself lives under e.g. ~/.docker isn't all secret
+        let inner = resolved.strip_prefix(dir).unwrap_or(&resolved);
+        if is_secret(inner) && !self.exposed.iter().any(|p| resolved.starts_with(p)) {
+            return Err(format!(
+                "{} may contain secrets (.env files, keys, ~/.ssh and the like); \
+                 the user can allow it with --expose",
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a file met while walking may be shown (no error message needed)
+    pub fn permits(&self, path: &Path) -> bool {
+        self.check(path).is_ok()
+    }
+
+    /// The allowed directory `path` lies in
+    fn containing(&self, path: &Path) -> Option<&Path> {
+        std::iter::once(&self.root)
+            .chain(&self.extra)
+            .find(|dir| path.starts_with(dir))
+            .map(PathBuf::as_path)
+    }
+}
+
+impl Default for Workspace {
+    /// The current directory
+    fn default() -> Self {
+        let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self::new(&root).unwrap_or(Self {
+            root,
+            extra: Vec::new(),
+            exposed: Vec::new(),
+        })
+    }
+}
+
+fn canonical(path: &Path) -> Result<PathBuf, String> {
+    path.canonicalize()
+        .map_err(|e| format!("Cannot resolve {}: {}", path.display(), e))
+}
+
+/// Canonical form of `path`, which need not exist: the nearest existing
+/// ancestor is resolved and the rest appended
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    // symlink_metadata: a dangling symlink exists (and fails to resolve)
+    while existing.symlink_metadata().is_err() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+
+    let mut resolved = canonical(existing)?;
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    Ok(resolved)
+}
+
+fn is_secret(path: &Path) -> bool {
+    let in_secret_dir = path.components().any(|c| match c {
+        Component::Normal(name) => SECRET_DIRS.iter().any(|d| name == *d),
+        _ => false,
+    });
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let secret_name = SECRET_FILES.iter().any(|pattern| name_matches(pattern, name))
+        && !NOT_SECRET_FILES.contains(&name);
+    in_secret_dir || secret_name
+}
+
+/// `*.pem`, `.env.*` or an exact name
+fn name_matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() > prefix.len() + suffix.len() && name.starts_with(prefix) && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
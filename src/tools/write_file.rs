@@ -0,0 +1,94 @@
+//! Write File Tool
+//!
+//! Topic 10: Implementing Core Tools
+//!
+//! Creates a file or replaces its whole contents. Overwriting an existing
+//! file follows the same rule as edit_file: it must have been read first.
+
+use super::diff::unified_diff;
+use super::file_tracker::FileTracker;
+use super::{ToolExecutor, ToolOutput, Workspace};
+use crate::api::Tool;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+pub struct WriteFileTool {
+    tracker: FileTracker,
+    workspace: Workspace,
+}
+
+impl WriteFileTool {
+    pub fn new(tracker: FileTracker, workspace: Workspace) -> Self {
+        Self { tracker, workspace }
+    }
+}
+
+impl ToolExecutor for WriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn definition(&self) -> Tool {
+        Tool::new(
+            "write_file",
+            "Create a file, or overwrite an existing one with new contents. \
+             Existing files must have been read with read_file first; \
+             prefer edit_file for small changes. Returns a diff.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the file to write"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "Full contents of the file"
+                    }
+                },
+                "required": ["path", "content"]
+            }),
+        )
+    }
+
+    fn permission_targets(&self, input: &Value) -> Vec<String> {
+        input["path"].as_str().map(str::to_string).into_iter().collect()
+    }
+
+    fn execute(&self, input: Value) -> Result<ToolOutput, String> {
+        let path_str = input["path"]
+            .as_str()
+            .ok_or("Missing required parameter: path")?;
+        let content = input["content"]
+            .as_str()
+            .ok_or("Missing required parameter: content")?;
+
+        let path = Path::new(path_str);
+        self.workspace.check(path)?;
+        if path.is_dir() {
+            return Err(format!("{} is a directory, not a file", path.display()));
+        }
+
+        let original = if path.exists() {
+            self.tracker.check_fresh(path)?;
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        } else {
+            String::new()
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        self.tracker.record(path);
+
+        Ok(ToolOutput::text(unified_diff(path_str, &original, content)))
+    }
+
+    fn echo_output(&self) -> bool {
+        true
+    }
+}